@@ -5,6 +5,24 @@ set -euo pipefail
 EARLY_ADOPTER_ISSUER_DIR="$( cd "$( dirname "${BASH_SOURCE[0]}" )" && pwd )"
 cd "$EARLY_ADOPTER_ISSUER_DIR"
 
+# With --previous-release <git revision>, build the module of that revision as
+# early_adopter_issuer_previous.wasm.gz instead, used by the upgrade tests from the previous release.
+if [ "${1:-}" == "--previous-release" ]
+then
+    PREVIOUS_RELEASE_DIR="target/previous-release"
+    rm -rf "$PREVIOUS_RELEASE_DIR"
+    git worktree prune
+    git worktree add --detach "$PREVIOUS_RELEASE_DIR" "${2:?missing git revision of the previous release}"
+    # The frontend build looks up the canister ids of the local replica.
+    if [ -d .dfx ]
+    then
+        cp -r .dfx "$PREVIOUS_RELEASE_DIR/"
+    fi
+    "$PREVIOUS_RELEASE_DIR/build.sh"
+    cp "$PREVIOUS_RELEASE_DIR/early_adopter_issuer.wasm.gz" ./early_adopter_issuer_previous.wasm.gz
+    git worktree remove --force "$PREVIOUS_RELEASE_DIR"
+    exit 0
+fi
 
 # Build the frontend
 cd frontend/
@@ -13,11 +31,21 @@ npm ci
 npm run build 
 cd ../
 
+# With --test-clock, build the module used by the integration tests (which has the `set_time`
+# endpoint) as early_adopter_issuer_test.wasm.gz instead.
+FEATURES=""
+WASM_NAME="early_adopter_issuer"
+if [ "${1:-}" == "--test-clock" ]
+then
+    FEATURES="test-clock"
+    WASM_NAME="early_adopter_issuer_test"
+fi
+
 # Build the canister
-cargo build --release --target wasm32-unknown-unknown --manifest-path ./Cargo.toml -j1
-ic-wasm "target/wasm32-unknown-unknown/release/early_adopter_issuer.wasm" -o "./early_adopter_issuer.wasm" shrink
-ic-wasm early_adopter_issuer.wasm -o early_adopter_issuer.wasm metadata candid:service -f early_adopter_issuer.did -v public
+cargo build --release --target wasm32-unknown-unknown --manifest-path ./Cargo.toml --bin early_adopter_issuer --features "$FEATURES" -j1
+ic-wasm "target/wasm32-unknown-unknown/release/early_adopter_issuer.wasm" -o "./$WASM_NAME.wasm" shrink
+ic-wasm "$WASM_NAME.wasm" -o "$WASM_NAME.wasm" metadata candid:service -f early_adopter_issuer.did -v public
 # indicate support for certificate version 1 and 2 in the canister metadata
-ic-wasm early_adopter_issuer.wasm -o early_adopter_issuer.wasm metadata supported_certificate_versions -d "1,2" -v public
-gzip --no-name --force "early_adopter_issuer.wasm"
+ic-wasm "$WASM_NAME.wasm" -o "$WASM_NAME.wasm" metadata supported_certificate_versions -d "1,2" -v public
+gzip --no-name --force "$WASM_NAME.wasm"
 
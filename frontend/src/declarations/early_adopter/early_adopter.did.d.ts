@@ -103,7 +103,11 @@ export interface _SERVICE {
     { 'Ok' : AddEventResponse } |
       { 'Err' : RegisterError }
   >,
-  'configure' : ActorMethod<[IssuerConfig], undefined>,
+  'configure' : ActorMethod<
+    [IssuerConfig],
+    { 'Ok' : null } |
+      { 'Err' : RegisterError }
+  >,
   'derivation_origin' : ActorMethod<
     [DerivationOriginRequest],
     { 'Ok' : DerivationOriginData } |
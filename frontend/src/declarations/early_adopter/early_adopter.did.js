@@ -111,7 +111,11 @@ export const idlFactory = ({ IDL }) => {
         [IDL.Variant({ 'Ok' : AddEventResponse, 'Err' : RegisterError })],
         [],
       ),
-    'configure' : IDL.Func([IssuerConfig], [], []),
+    'configure' : IDL.Func(
+      [IssuerConfig],
+      [IDL.Variant({ 'Ok' : IDL.Null, 'Err' : RegisterError })],
+      [],
+    ),
     'derivation_origin' : IDL.Func(
         [DerivationOriginRequest],
         [
@@ -0,0 +1,91 @@
+//! Verifies a credential issued by the Early Adopter Issuer, without a replica.
+//!
+//! Usage:
+//!   verify_credential --vc-jws <JWS> --issuer-canister-id <PRINCIPAL> --credential-spec <JSON>
+//!       [--issuer-url <URL>] [--ic-root-key-der <HEX>]
+//!
+//! The credential spec is given as JSON, e.g.
+//!   {"credential_type":"EarlyAdopter","arguments":{"sinceYear":{"Int":2024}}}
+//! The IC root key defaults to the mainnet root key, the issuer URL to https://attendance.vc.
+//! Exits with 0 if the credential is valid, 1 if it is not, and 2 on usage errors.
+
+use candid::Principal;
+use canister_sig_util::{extract_raw_root_pk_from_der, IC_ROOT_PK_DER};
+use early_adopter_issuer_lib::verification::verify_credential;
+use early_adopter_issuer_lib::DEFAULT_ISSUER_URL;
+use std::collections::HashMap;
+use std::process::exit;
+use std::time::{SystemTime, UNIX_EPOCH};
+use vc_util::issuer_api::CredentialSpec;
+
+const OPTIONS: [&str; 5] = [
+    "--vc-jws",
+    "--issuer-canister-id",
+    "--credential-spec",
+    "--issuer-url",
+    "--ic-root-key-der",
+];
+const USAGE: &str = "Usage: verify_credential --vc-jws <JWS> --issuer-canister-id <PRINCIPAL> --credential-spec <JSON> [--issuer-url <URL>] [--ic-root-key-der <HEX>]";
+
+fn usage_error(msg: &str) -> ! {
+    eprintln!("{}\n{}", msg, USAGE);
+    exit(2)
+}
+
+fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if args.len() % 2 != 0 {
+        usage_error("Every option needs a value.");
+    }
+    let mut options: HashMap<&str, &str> = HashMap::new();
+    for pair in args.chunks(2) {
+        let (option, value) = (pair[0].as_str(), pair[1].as_str());
+        if !OPTIONS.contains(&option) {
+            usage_error(&format!("Unknown option {}.", option));
+        }
+        if options.insert(option, value).is_some() {
+            usage_error(&format!("Duplicate option {}.", option));
+        }
+    }
+    let required = |option: &str| {
+        options
+            .get(option)
+            .copied()
+            .unwrap_or_else(|| usage_error(&format!("Missing option {}.", option)))
+    };
+    let vc_jws = required("--vc-jws");
+    let issuer_canister_id = Principal::from_text(required("--issuer-canister-id"))
+        .unwrap_or_else(|e| usage_error(&format!("Invalid issuer canister id: {}", e)));
+    let credential_spec: CredentialSpec = serde_json::from_str(required("--credential-spec"))
+        .unwrap_or_else(|e| usage_error(&format!("Invalid credential spec: {}", e)));
+    let issuer_url = options
+        .get("--issuer-url")
+        .copied()
+        .unwrap_or(DEFAULT_ISSUER_URL);
+    let ic_root_key_der = match options.get("--ic-root-key-der") {
+        Some(key_hex) => hex::decode(key_hex)
+            .unwrap_or_else(|e| usage_error(&format!("Invalid IC root key: {}", e))),
+        None => IC_ROOT_PK_DER.to_vec(),
+    };
+    let ic_root_key_raw = extract_raw_root_pk_from_der(&ic_root_key_der)
+        .unwrap_or_else(|e| usage_error(&format!("Invalid IC root key: {}", e)));
+    let current_time_ns = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .expect("system time is before the UNIX epoch")
+        .as_nanos();
+
+    match verify_credential(
+        vc_jws,
+        &ic_root_key_raw,
+        issuer_canister_id,
+        issuer_url,
+        &credential_spec,
+        current_time_ns,
+    ) {
+        Ok(()) => println!("Credential is valid."),
+        Err(err) => {
+            println!("Credential is invalid: {}", err);
+            exit(1)
+        }
+    }
+}
@@ -0,0 +1,65 @@
+//! Consent messages shown to the user before a credential is issued.
+
+use crate::credential_spec::{verify_credential_spec, SupportedCredentialType};
+use vc_util::issuer_api::{CredentialSpec, Icrc21ConsentInfo, Icrc21Error, Icrc21ErrorInfo};
+
+pub fn get_vc_consent_message_en(
+    credential_spec: &CredentialSpec,
+) -> Result<Icrc21ConsentInfo, Icrc21Error> {
+    match verify_credential_spec(credential_spec) {
+        Ok(SupportedCredentialType::EarlyAdopter(since_year)) => Ok(Icrc21ConsentInfo {
+            consent_message: format!("You became an early adopter in {}.", since_year),
+            language: "en".to_string(),
+        }),
+        Ok(SupportedCredentialType::EventAttendance(event_name)) => Ok(Icrc21ConsentInfo {
+            consent_message: format!("You have attended the event {}.", event_name),
+            language: "en".to_string(),
+        }),
+        Err(err) => Err(Icrc21Error::ConsentMessageUnavailable(Icrc21ErrorInfo {
+            description: format!("Credential spec not supported: {:?}", err),
+        })),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::credential_spec::credential_spec;
+    use vc_util::issuer_api::ArgumentValue;
+
+    #[test]
+    fn should_render_early_adopter_consent_message() {
+        let info = get_vc_consent_message_en(&credential_spec(
+            "EarlyAdopter",
+            &[("sinceYear", ArgumentValue::Int(2025))],
+        ))
+        .expect("failed to get consent message");
+        assert_eq!(info.consent_message, "You became an early adopter in 2025.");
+        assert_eq!(info.language, "en");
+    }
+
+    #[test]
+    fn should_render_event_attendance_consent_message() {
+        let info = get_vc_consent_message_en(&credential_spec(
+            "EventAttendance",
+            &[("eventName", ArgumentValue::String("DICE2024".to_string()))],
+        ))
+        .expect("failed to get consent message");
+        assert_eq!(
+            info.consent_message,
+            "You have attended the event DICE2024."
+        );
+    }
+
+    #[test]
+    fn should_not_render_consent_message_for_unsupported_spec() {
+        let result = get_vc_consent_message_en(&credential_spec(
+            "EarlyAdopter",
+            &[("sinceYear", ArgumentValue::Int(2023))],
+        ));
+        assert!(matches!(
+            result,
+            Err(Icrc21Error::ConsentMessageUnavailable(_))
+        ));
+    }
+}
@@ -0,0 +1,59 @@
+//! Time-dependent parameters of the issued credentials.
+
+use candid::Principal;
+
+const CREDENTIAL_URL_PREFIX: &str = "data:text/plain;charset=UTF-8,";
+const MINUTE_NS: u64 = 60 * 1_000_000_000;
+// The expiration of issued verifiable credentials.
+pub const VC_EXPIRATION_PERIOD_NS: u64 = 15 * MINUTE_NS;
+
+/// Source of the current time.
+pub trait Clock {
+    /// Current time in nanoseconds since the UNIX epoch.
+    fn now_ns(&self) -> u64;
+}
+
+/// Expiration timestamp (in seconds) of a credential issued now.
+pub fn exp_timestamp_s(clock: &impl Clock) -> u32 {
+    ((clock.now_ns() + VC_EXPIRATION_PERIOD_NS) / 1_000_000_000) as u32
+}
+
+/// Unique id of a credential issued now to `subject`, as a URL with the format:
+/// "data:text/plain;charset=UTF-8,issuer:...,timestamp_ns:...,subject:..."
+pub fn data_url_credential_id(issuer_url: &str, subject: Principal, clock: &impl Clock) -> String {
+    let issuer = format!("issuer:{}", issuer_url);
+    let timestamp = format!("timestamp_ns:{}", clock.now_ns());
+    let subject = format!("subject:{}", subject.to_text());
+    format!(
+        "{}{},{},{}",
+        CREDENTIAL_URL_PREFIX, issuer, timestamp, subject
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct FixedClock(u64);
+
+    impl Clock for FixedClock {
+        fn now_ns(&self) -> u64 {
+            self.0
+        }
+    }
+
+    #[test]
+    fn should_expire_after_expiration_period() {
+        let clock = FixedClock(1_700_000_000_000_000_000);
+        assert_eq!(exp_timestamp_s(&clock), 1_700_000_000 + 15 * 60);
+    }
+
+    #[test]
+    fn should_build_data_url_credential_id() {
+        let subject = Principal::anonymous();
+        assert_eq!(
+            data_url_credential_id("https://attendance.vc", subject, &FixedClock(42)),
+            "data:text/plain;charset=UTF-8,issuer:https://attendance.vc,timestamp_ns:42,subject:2vxsx-fae"
+        );
+    }
+}
@@ -0,0 +1,198 @@
+//! Parsing of the credential specs supported by the issuer.
+
+use vc_util::issuer_api::{ArgumentValue, CredentialSpec, IssueCredentialError};
+
+// End of year 2024 as UNIX timestamp.
+const EOY_2024_TIMESTAMP_S: u32 = 1735685999;
+
+#[derive(Debug)]
+pub enum SupportedCredentialType {
+    // Early adopter since <year>
+    EarlyAdopter(i32),
+    // Attendace to specific event
+    EventAttendance(String),
+}
+
+pub fn verify_credential_spec(
+    spec: &CredentialSpec,
+) -> Result<SupportedCredentialType, IssueCredentialError> {
+    match spec.credential_type.as_str() {
+        "EarlyAdopter" => {
+            let since_year = verify_early_adopter_spec_and_get_since_year(spec)
+                .map_err(IssueCredentialError::UnsupportedCredentialSpec)?;
+            Ok(SupportedCredentialType::EarlyAdopter(since_year))
+        }
+        "EventAttendance" => {
+            let event_name = verify_event_attendance_and_get_event_name(spec)
+                .map_err(IssueCredentialError::UnsupportedCredentialSpec)?;
+            Ok(SupportedCredentialType::EventAttendance(event_name.clone()))
+        }
+        other => Err(IssueCredentialError::UnsupportedCredentialSpec(format!(
+            "Credential {} is not supported",
+            other
+        ))),
+    }
+}
+
+// Note: not very accurate, but we cannot depend on `chrono` due to WASM-ability.
+// Assumption: `year` is at least 2024 (as checked before calling this function)
+// Saturates at `u32::MAX` for years beyond the range of the timestamps.
+pub fn year_to_max_timestamp_s(year: i32) -> u32 {
+    const SECONDS_IN_YEAR: u32 = 31_536_000;
+    EOY_2024_TIMESTAMP_S.saturating_add(((year - 2024) as u32).saturating_mul(SECONDS_IN_YEAR))
+}
+
+fn verify_early_adopter_spec_and_get_since_year(spec: &CredentialSpec) -> Result<i32, String> {
+    if spec.credential_type.as_str() == "EarlyAdopter" {
+        let Some(arguments) = &spec.arguments else {
+            return Err("Credential spec has no arguments".to_string());
+        };
+        let expected_argument = "sinceYear";
+        let Some(value) = arguments.get(expected_argument) else {
+            return Err(format!(
+                "Credential spec has no {}-argument",
+                expected_argument
+            ));
+        };
+        if arguments.len() != 1 {
+            return Err("Credential spec has unexpected arguments".to_string());
+        }
+        let ArgumentValue::Int(year) = value else {
+            return Err(format!(
+                "Credential spec has unexpected value for {}-argument",
+                expected_argument
+            ));
+        };
+        if *year < 2024 {
+            return Err(format!(
+                "Credential spec has unsupported value for {}-argument",
+                expected_argument
+            ));
+        };
+
+        Ok(*year)
+    } else {
+        Err(format!(
+            "Credential {} is not supported",
+            spec.credential_type.as_str()
+        ))
+    }
+}
+
+fn verify_event_attendance_and_get_event_name(spec: &CredentialSpec) -> Result<&String, String> {
+    if spec.credential_type.as_str() == "EventAttendance" {
+        let Some(arguments) = &spec.arguments else {
+            return Err("Credential spec has no arguments".to_string());
+        };
+        let expected_argument = "eventName";
+        let Some(value) = arguments.get(expected_argument) else {
+            return Err(format!(
+                "Credential spec has no {}-argument",
+                expected_argument
+            ));
+        };
+        if arguments.len() != 1 {
+            return Err("Credential spec has unexpected arguments".to_string());
+        }
+        let ArgumentValue::String(event_name) = value else {
+            return Err(format!(
+                "Credential spec has unexpected value for {}-argument",
+                expected_argument
+            ));
+        };
+        Ok(event_name)
+    } else {
+        Err(format!(
+            "Credential {} is not supported",
+            spec.credential_type.as_str()
+        ))
+    }
+}
+
+/// Builds the credential spec of the given type with the given arguments, for the unit tests.
+#[cfg(test)]
+pub(crate) fn credential_spec(
+    credential_type: &str,
+    arguments: &[(&str, ArgumentValue)],
+) -> CredentialSpec {
+    CredentialSpec {
+        credential_type: credential_type.to_string(),
+        arguments: Some(
+            arguments
+                .iter()
+                .map(|(name, value)| (name.to_string(), value.clone()))
+                .collect::<std::collections::HashMap<_, _>>(),
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_parse_early_adopter_spec() {
+        assert!(matches!(
+            verify_credential_spec(&credential_spec(
+                "EarlyAdopter",
+                &[("sinceYear", ArgumentValue::Int(2025))]
+            )),
+            Ok(SupportedCredentialType::EarlyAdopter(2025))
+        ));
+    }
+
+    #[test]
+    fn should_parse_event_attendance_spec() {
+        let result = verify_credential_spec(&credential_spec(
+            "EventAttendance",
+            &[("eventName", ArgumentValue::String("DICE2024".to_string()))],
+        ));
+        assert!(matches!(
+            result,
+            Ok(SupportedCredentialType::EventAttendance(event_name)) if event_name == "DICE2024"
+        ));
+    }
+
+    #[test]
+    fn should_reject_invalid_specs() {
+        for invalid_spec in [
+            credential_spec("Unknown", &[("sinceYear", ArgumentValue::Int(2024))]),
+            credential_spec("EarlyAdopter", &[]),
+            credential_spec("EarlyAdopter", &[("sinceYear", ArgumentValue::Int(2023))]),
+            credential_spec(
+                "EarlyAdopter",
+                &[("sinceYear", ArgumentValue::String("2024".to_string()))],
+            ),
+            credential_spec(
+                "EarlyAdopter",
+                &[
+                    ("sinceYear", ArgumentValue::Int(2024)),
+                    ("other", ArgumentValue::Int(1)),
+                ],
+            ),
+            credential_spec("EventAttendance", &[("eventName", ArgumentValue::Int(1))]),
+            credential_spec(
+                "EventAttendance",
+                &[("sinceYear", ArgumentValue::Int(2024))],
+            ),
+            CredentialSpec {
+                credential_type: "EventAttendance".to_string(),
+                arguments: None,
+            },
+        ] {
+            assert!(matches!(
+                verify_credential_spec(&invalid_spec),
+                Err(IssueCredentialError::UnsupportedCredentialSpec(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn should_convert_year_to_max_timestamp() {
+        assert_eq!(year_to_max_timestamp_s(2024), EOY_2024_TIMESTAMP_S);
+        assert_eq!(
+            year_to_max_timestamp_s(2025),
+            EOY_2024_TIMESTAMP_S + 31_536_000
+        );
+    }
+}
@@ -0,0 +1,145 @@
+//! Checks whether a user is eligible for a credential, independently of how the
+//! registrations are stored.
+
+use crate::credential_spec::{year_to_max_timestamp_s, SupportedCredentialType};
+use candid::Principal;
+use vc_util::issuer_api::IssueCredentialError;
+
+/// Read access to the registrations of the users.
+pub trait RegistrationStore {
+    /// Timestamp (in seconds) at which the user registered as an early adopter, if at all.
+    fn joined_timestamp_s(&self, user: Principal) -> Option<u32>;
+    /// Whether the user registered for the given event.
+    fn attended_event(&self, user: Principal, event_name: &str) -> bool;
+}
+
+/// Checks that `user` is eligible for a credential of the given type.
+pub fn verify_eligibility(
+    store: &impl RegistrationStore,
+    user: Principal,
+    credential_type: &SupportedCredentialType,
+) -> Result<(), IssueCredentialError> {
+    let Some(joined_timestamp_s) = store.joined_timestamp_s(user) else {
+        return Err(IssueCredentialError::UnauthorizedSubject(format!(
+            "unregistered principal {}",
+            user.to_text()
+        )));
+    };
+    match credential_type {
+        SupportedCredentialType::EarlyAdopter(since_year) => {
+            if joined_timestamp_s < year_to_max_timestamp_s(*since_year) {
+                Ok(())
+            } else {
+                Err(IssueCredentialError::UnauthorizedSubject(format!(
+                    "unauthorized principal {}",
+                    user.to_text()
+                )))
+            }
+        }
+        SupportedCredentialType::EventAttendance(event_name) => {
+            if store.attended_event(user, event_name) {
+                Ok(())
+            } else {
+                Err(IssueCredentialError::UnauthorizedSubject(format!(
+                    "User {} has not attended event {}",
+                    user.to_text(),
+                    event_name
+                )))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::{HashMap, HashSet};
+
+    // Start of year 2024 as UNIX timestamp.
+    const SOY_2024_TIMESTAMP_S: u32 = 1704067200;
+
+    #[derive(Default)]
+    struct InMemoryStore {
+        joined_timestamps_s: HashMap<Principal, u32>,
+        attendances: HashSet<(Principal, String)>,
+    }
+
+    impl RegistrationStore for InMemoryStore {
+        fn joined_timestamp_s(&self, user: Principal) -> Option<u32> {
+            self.joined_timestamps_s.get(&user).copied()
+        }
+
+        fn attended_event(&self, user: Principal, event_name: &str) -> bool {
+            self.attendances.contains(&(user, event_name.to_string()))
+        }
+    }
+
+    fn user() -> Principal {
+        Principal::self_authenticating(b"user")
+    }
+
+    fn store_with_user(joined_timestamp_s: u32, events: &[&str]) -> InMemoryStore {
+        InMemoryStore {
+            joined_timestamps_s: HashMap::from([(user(), joined_timestamp_s)]),
+            attendances: events
+                .iter()
+                .map(|event_name| (user(), event_name.to_string()))
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn should_reject_unregistered_user() {
+        for credential_type in [
+            SupportedCredentialType::EarlyAdopter(2024),
+            SupportedCredentialType::EventAttendance("DICE2024".to_string()),
+        ] {
+            assert!(matches!(
+                verify_eligibility(&InMemoryStore::default(), user(), &credential_type),
+                Err(IssueCredentialError::UnauthorizedSubject(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn should_check_early_adopter_since_year() {
+        let store = store_with_user(SOY_2024_TIMESTAMP_S, &[]);
+        assert!(
+            verify_eligibility(&store, user(), &SupportedCredentialType::EarlyAdopter(2024))
+                .is_ok()
+        );
+        assert!(
+            verify_eligibility(&store, user(), &SupportedCredentialType::EarlyAdopter(2030))
+                .is_ok()
+        );
+
+        let store = store_with_user(year_to_max_timestamp_s(2024), &[]);
+        assert!(matches!(
+            verify_eligibility(&store, user(), &SupportedCredentialType::EarlyAdopter(2024)),
+            Err(IssueCredentialError::UnauthorizedSubject(_))
+        ));
+        assert!(
+            verify_eligibility(&store, user(), &SupportedCredentialType::EarlyAdopter(2025))
+                .is_ok()
+        );
+    }
+
+    #[test]
+    fn should_check_event_attendance() {
+        let store = store_with_user(SOY_2024_TIMESTAMP_S, &["DICE2024"]);
+        assert!(verify_eligibility(
+            &store,
+            user(),
+            &SupportedCredentialType::EventAttendance("DICE2024".to_string())
+        )
+        .is_ok());
+        assert!(matches!(
+            verify_eligibility(
+                &store,
+                user(),
+                &SupportedCredentialType::EventAttendance("DICE2025".to_string())
+            ),
+            Err(IssueCredentialError::UnauthorizedSubject(_))
+        ));
+    }
+}
@@ -0,0 +1,260 @@
+//! Assets served over HTTP by the issuer, certified for both versions of the response
+//! verification: v1 certifies the body of a response, v2 certifies the body and the headers.
+//!
+//! Unlike `asset_util::CertifiedAssets`, the assets can be re-certified one at a time, and
+//! each asset is certified with its own headers (e.g. any content type).
+
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use ic_certification::{
+    fork, fork_hash, labeled, labeled_hash, pruned, AsHashTree, Hash, HashTree, RbTree,
+};
+use ic_http_certification::utils::add_v2_certificate_header;
+use ic_http_certification::{
+    DefaultCelBuilder, DefaultResponseCertification, HttpCertification, HttpCertificationPath,
+    HttpCertificationTree, HttpCertificationTreeEntry, HttpResponse,
+};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+
+/// Label of the v1 certification tree, mapping the URL paths to the hash of their body.
+const LABEL_ASSETS_V1: &[u8] = b"http_assets";
+const CERTIFICATE_HEADER_NAME: &str = "IC-Certificate";
+const CERTIFICATE_EXPRESSION_HEADER_NAME: &str = "IC-CertificateExpression";
+
+pub type HeaderField = (String, String);
+
+struct CertifiedAsset {
+    // Headers of the response, including the certificate expression header.
+    headers: Vec<HeaderField>,
+    body: Vec<u8>,
+    certification: HttpCertification,
+}
+
+#[derive(Default)]
+pub struct CertifiedHttpAssets {
+    assets: HashMap<String, CertifiedAsset>,
+    tree_v1: RbTree<String, Hash>,
+    tree_v2: HttpCertificationTree,
+}
+
+impl CertifiedHttpAssets {
+    /// Certifies the asset served at `url_path` with the given headers and body, replacing the
+    /// asset previously certified at the same path (if any).
+    pub fn certify_asset(&mut self, url_path: &str, headers: Vec<HeaderField>, body: Vec<u8>) {
+        self.remove_asset(url_path);
+
+        let header_names: Vec<String> = headers.iter().map(|(name, _)| name.clone()).collect();
+        let certified_headers: Vec<&str> = header_names.iter().map(String::as_str).collect();
+        let cel_expr = DefaultCelBuilder::response_only_certification()
+            .with_response_certification(DefaultResponseCertification::certified_response_headers(
+                &certified_headers,
+            ))
+            .build();
+        let mut headers = headers;
+        headers.push((
+            CERTIFICATE_EXPRESSION_HEADER_NAME.to_string(),
+            cel_expr.to_string(),
+        ));
+        let response = HttpResponse {
+            status_code: 200,
+            headers,
+            body,
+            upgrade: None,
+        };
+        let certification = HttpCertification::response_only(&cel_expr, &response, None)
+            .expect("failed to certify asset");
+
+        let path = HttpCertificationPath::exact(url_path);
+        self.tree_v2
+            .insert(&HttpCertificationTreeEntry::new(&path, &certification));
+        self.tree_v1
+            .insert(url_path.to_string(), body_hash(&response.body));
+        self.assets.insert(
+            url_path.to_string(),
+            CertifiedAsset {
+                headers: response.headers,
+                body: response.body,
+                certification,
+            },
+        );
+    }
+
+    /// Removes the asset served at `url_path` (if any) from the certified assets.
+    pub fn remove_asset(&mut self, url_path: &str) {
+        if let Some(asset) = self.assets.remove(url_path) {
+            let path = HttpCertificationPath::exact(url_path);
+            self.tree_v2.delete(&HttpCertificationTreeEntry::new(
+                &path,
+                &asset.certification,
+            ));
+            self.tree_v1.delete(url_path.as_bytes());
+        }
+    }
+
+    /// Hash of the certified assets, to be combined into the certified data of the canister
+    /// as `fork_hash(root_hash(), other_hash)`.
+    pub fn root_hash(&self) -> Hash {
+        // NB: Labels added in lexicographic order.
+        fork_hash(
+            &labeled_hash(LABEL_ASSETS_V1, &self.tree_v1.root_hash()),
+            &self.tree_v2.root_hash(),
+        )
+    }
+
+    /// Returns the headers and body of the response serving the asset at `url_path`, with the
+    /// certificate headers of the highest supported version up to `certificate_version`.
+    /// `other_witness` is the witness of the `other_hash` next to `root_hash()` in the
+    /// certified data, see `root_hash`.
+    pub fn certified_response(
+        &self,
+        url_path: &str,
+        certificate_version: Option<u16>,
+        data_certificate: &[u8],
+        other_witness: HashTree,
+    ) -> Option<(Vec<HeaderField>, Vec<u8>)> {
+        let asset = self.assets.get(url_path)?;
+        let v2 = certificate_version.unwrap_or(1) >= 2;
+        let witness = fork(self.witness(url_path, asset, v2), other_witness);
+        if v2 {
+            let mut response = HttpResponse {
+                status_code: 200,
+                headers: asset.headers.clone(),
+                body: asset.body.clone(),
+                upgrade: None,
+            };
+            add_v2_certificate_header(
+                data_certificate,
+                &mut response,
+                &witness,
+                &HttpCertificationPath::exact(url_path).to_expr_path(),
+            );
+            return Some((response.headers, response.body));
+        }
+
+        let mut headers = asset.headers.clone();
+        headers.push((
+            CERTIFICATE_HEADER_NAME.to_string(),
+            format!(
+                "certificate=:{}:, tree=:{}:",
+                STANDARD.encode(data_certificate),
+                STANDARD.encode(encode_witness(&witness))
+            ),
+        ));
+        Some((headers, asset.body.clone()))
+    }
+
+    // Witness of the asset in the tree of `root_hash()`, for certification v2 or v1.
+    fn witness(&self, url_path: &str, asset: &CertifiedAsset, v2: bool) -> HashTree {
+        if v2 {
+            let path = HttpCertificationPath::exact(url_path);
+            let entry = HttpCertificationTreeEntry::new(&path, &asset.certification);
+            let expr_witness = self
+                .tree_v2
+                .witness(&entry, url_path)
+                .expect("failed to witness asset");
+            fork(
+                pruned(labeled_hash(LABEL_ASSETS_V1, &self.tree_v1.root_hash())),
+                expr_witness,
+            )
+        } else {
+            fork(
+                labeled(LABEL_ASSETS_V1, self.tree_v1.witness(url_path.as_bytes())),
+                pruned(self.tree_v2.root_hash()),
+            )
+        }
+    }
+}
+
+fn body_hash(body: &[u8]) -> Hash {
+    Sha256::digest(body).into()
+}
+
+fn encode_witness(witness: &HashTree) -> Vec<u8> {
+    let mut serializer = serde_cbor::Serializer::new(vec![]);
+    serializer
+        .self_describe()
+        .expect("failed to encode witness");
+    witness
+        .serialize(&mut serializer)
+        .expect("failed to encode witness");
+    serializer.into_inner()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ic_certification::empty;
+
+    const METRICS_CONTENT_TYPE: &str = "text/plain; version=0.0.4";
+
+    fn metrics_headers() -> Vec<HeaderField> {
+        vec![("Content-Type".to_string(), METRICS_CONTENT_TYPE.to_string())]
+    }
+
+    fn assets() -> CertifiedHttpAssets {
+        let mut assets = CertifiedHttpAssets::default();
+        assets.certify_asset(
+            "/index.html",
+            vec![("Content-Type".to_string(), "text/html".to_string())],
+            b"<html></html>".to_vec(),
+        );
+        assets.certify_asset("/metrics", metrics_headers(), b"metric 1".to_vec());
+        assets
+    }
+
+    fn assert_witnesses_root_hash(assets: &CertifiedHttpAssets, url_path: &str) {
+        let asset = assets.assets.get(url_path).expect("missing asset");
+        for v2 in [false, true] {
+            assert_eq!(
+                assets.witness(url_path, asset, v2).digest(),
+                assets.root_hash()
+            );
+        }
+    }
+
+    #[test]
+    fn should_serve_the_certified_headers() {
+        let assets = assets();
+        for certificate_version in [None, Some(1), Some(2)] {
+            let (headers, body) = assets
+                .certified_response("/metrics", certificate_version, b"certificate", empty())
+                .expect("missing asset");
+            assert_eq!(body, b"metric 1");
+            assert!(
+                headers.contains(&("Content-Type".to_string(), METRICS_CONTENT_TYPE.to_string()))
+            );
+            assert!(headers
+                .iter()
+                .any(|(name, _)| name == CERTIFICATE_HEADER_NAME));
+        }
+        assert_witnesses_root_hash(&assets, "/metrics");
+        assert_witnesses_root_hash(&assets, "/index.html");
+    }
+
+    #[test]
+    fn should_replace_a_single_asset() {
+        let mut assets = assets();
+        let root_hash = assets.root_hash();
+
+        assets.certify_asset("/metrics", metrics_headers(), b"metric 2".to_vec());
+        assert_ne!(assets.root_hash(), root_hash);
+        let (_, body) = assets
+            .certified_response("/metrics", Some(2), b"certificate", empty())
+            .expect("missing asset");
+        assert_eq!(body, b"metric 2");
+        assert_witnesses_root_hash(&assets, "/metrics");
+        assert_witnesses_root_hash(&assets, "/index.html");
+
+        assets.certify_asset("/metrics", metrics_headers(), b"metric 1".to_vec());
+        assert_eq!(assets.root_hash(), root_hash);
+    }
+
+    #[test]
+    fn should_not_serve_unknown_assets() {
+        assert!(assets()
+            .certified_response("/unknown", Some(2), b"certificate", empty())
+            .is_none());
+    }
+}
@@ -0,0 +1,13 @@
+//! Library part of the Early Adopter Issuer: the logic of the issuer that does not depend on
+//! the canister (spec parsing, eligibility checks, consent messages), and the off-chain
+//! verification of the issued credentials.
+
+pub mod consent_message;
+pub mod credential;
+pub mod credential_spec;
+pub mod eligibility;
+pub mod http_assets;
+pub mod verification;
+
+// Issuer URL used unless configured otherwise.
+pub const DEFAULT_ISSUER_URL: &str = "https://attendance.vc";
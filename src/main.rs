@@ -1,54 +1,176 @@
-use asset_util::{collect_assets, CertifiedAssets};
+use asset_util::{collect_assets, ContentEncoding, ContentType};
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
 use candid::{candid_method, CandidType, Deserialize, Principal};
 use canister_sig_util::signature_map::{SignatureMap, LABEL_SIG};
 use canister_sig_util::{extract_raw_root_pk_from_der, CanisterSigPublicKey, IC_ROOT_PK_DER};
+use early_adopter_issuer_lib::consent_message::get_vc_consent_message_en;
+use early_adopter_issuer_lib::credential::{data_url_credential_id, exp_timestamp_s, Clock};
+use early_adopter_issuer_lib::credential_spec::verify_credential_spec;
+use early_adopter_issuer_lib::eligibility::{verify_eligibility, RegistrationStore};
+use early_adopter_issuer_lib::http_assets::CertifiedHttpAssets;
+use early_adopter_issuer_lib::verification::verify_credential_for_subject;
+use early_adopter_issuer_lib::DEFAULT_ISSUER_URL;
+use ic_cdk::api::call::{accept_message, arg_data, arg_data_raw_size, method_name};
 use ic_cdk::api::management_canister::main::raw_rand;
-use ic_cdk::api::{caller, is_controller, set_certified_data, time};
+use ic_cdk::api::{caller, data_certificate, is_controller, set_certified_data, time};
 use ic_cdk_macros::post_upgrade;
-use ic_cdk_macros::{init, query, update};
+use ic_cdk_macros::{init, inspect_message, query, update};
 use ic_certification::{fork_hash, labeled_hash, pruned, Hash};
 use ic_metrics_encoder::MetricsEncoder;
 use ic_stable_structures::memory_manager::{MemoryId, MemoryManager, VirtualMemory};
 use ic_stable_structures::storable::{Bound, Storable};
-use ic_stable_structures::{DefaultMemoryImpl, RestrictedMemory, StableBTreeMap, StableCell};
+use ic_stable_structures::{
+    DefaultMemoryImpl, RestrictedMemory, StableBTreeMap, StableCell, StableLog,
+};
+use identity_credential::validator::JwtValidationError;
 use include_dir::{include_dir, Dir};
 use lazy_static::lazy_static;
+use serde::Serialize;
 use serde_bytes::ByteBuf;
 use sha2::{Digest, Sha256};
 use std::borrow::Cow;
 use std::cell::RefCell;
 use std::collections::BTreeMap;
+use std::ops::Bound as RangeBound;
+use std::time::Duration;
 use vc_util::issuer_api::{
     ArgumentValue, CredentialSpec, DerivationOriginData, DerivationOriginError,
-    DerivationOriginRequest, GetCredentialRequest, Icrc21ConsentInfo, Icrc21Error, Icrc21ErrorInfo,
+    DerivationOriginRequest, GetCredentialRequest, Icrc21ConsentInfo, Icrc21Error,
     Icrc21VcConsentMessageRequest, IssueCredentialError, IssuedCredentialData,
     PrepareCredentialRequest, PreparedCredentialData, SignedIdAlias,
 };
 use vc_util::{
-    build_credential_jwt, did_for_principal, get_verified_id_alias_from_jws, vc_jwt_to_jws,
-    vc_signing_input, vc_signing_input_hash, AliasTuple, CredentialParams,
+    build_credential_jwt, did_for_principal, vc_jwt_to_jws, vc_signing_input,
+    vc_signing_input_hash, verify_credential_jws_with_canister_id, AliasTuple, CredentialParams,
 };
 
 /// We use restricted memory in order to ensure the separation between non-managed config memory (first page)
 /// and the managed memory for potential other data of the canister.
 type Memory = RestrictedMemory<DefaultMemoryImpl>;
 type ConfigCell = StableCell<IssuerConfig, Memory>;
-type EarlyAdoptersMap = StableBTreeMap<Principal, EarlyAdopterData, VirtualMemory<Memory>>;
+type LegacyEarlyAdoptersMap = StableBTreeMap<Principal, EarlyAdopterData, VirtualMemory<Memory>>;
+type EarlyAdoptersMap = StableBTreeMap<Principal, EarlyAdopterRecord, VirtualMemory<Memory>>;
+type UserEventsMap = StableBTreeMap<UserEventKey, u32, VirtualMemory<Memory>>;
 type EventName = String;
+type EventId = u32;
+type EventIdsMap = StableBTreeMap<EventName, EventId, VirtualMemory<Memory>>;
+type EventNamesMap = StableBTreeMap<EventId, EventName, VirtualMemory<Memory>>;
 type EventsMap = StableBTreeMap<EventName, EventRecord, VirtualMemory<Memory>>;
-
-const EARLY_ADOPTERS_MEMORY_ID: MemoryId = MemoryId::new(0u8);
+type EventAttendeesMap = StableBTreeMap<EventAttendeeKey, UserEventRecord, VirtualMemory<Memory>>;
+type EventAttendeeCountsMap = StableBTreeMap<EventId, u64, VirtualMemory<Memory>>;
+type IssuanceLog = StableLog<IssuanceRecord, VirtualMemory<Memory>, VirtualMemory<Memory>>;
+type IssuanceSubjectsMap = StableBTreeMap<u64, IssuanceSubject, VirtualMemory<Memory>>;
+type UserIssuancesMap = StableBTreeMap<UserIssuanceKey, (), VirtualMemory<Memory>>;
+type DeletionLog = StableLog<DeletionRecord, VirtualMemory<Memory>, VirtualMemory<Memory>>;
+type SchemaVersionCell = StableCell<u32, VirtualMemory<Memory>>;
+
+const LEGACY_EARLY_ADOPTERS_MEMORY_ID: MemoryId = MemoryId::new(0u8);
 const EVENTS_MEMORY_ID: MemoryId = MemoryId::new(1u8);
+const ISSUANCE_LOG_INDEX_MEMORY_ID: MemoryId = MemoryId::new(2u8);
+const ISSUANCE_LOG_DATA_MEMORY_ID: MemoryId = MemoryId::new(3u8);
+const EVENT_ATTENDEES_MEMORY_ID: MemoryId = MemoryId::new(4u8);
+const DELETION_LOG_INDEX_MEMORY_ID: MemoryId = MemoryId::new(5u8);
+const DELETION_LOG_DATA_MEMORY_ID: MemoryId = MemoryId::new(6u8);
+const SCHEMA_VERSION_MEMORY_ID: MemoryId = MemoryId::new(7u8);
+const EARLY_ADOPTERS_MEMORY_ID: MemoryId = MemoryId::new(8u8);
+const USER_EVENTS_MEMORY_ID: MemoryId = MemoryId::new(9u8);
+const EVENT_IDS_MEMORY_ID: MemoryId = MemoryId::new(10u8);
+const EVENT_NAMES_MEMORY_ID: MemoryId = MemoryId::new(11u8);
+const EVENT_ATTENDEE_COUNTS_MEMORY_ID: MemoryId = MemoryId::new(12u8);
+const ISSUANCE_SUBJECTS_MEMORY_ID: MemoryId = MemoryId::new(13u8);
+const USER_ISSUANCES_MEMORY_ID: MemoryId = MemoryId::new(14u8);
 
-const ISSUER_URL: &str = "https://attendance.vc";
-const CREDENTIAL_URL_PREFIX: &str = "data:text/plain;charset=UTF-8,";
-
-const MINUTE_NS: u64 = 60 * 1_000_000_000;
 const PROD_II_CANISTER_ID: &str = "rdmx6-jaaaa-aaaaa-aaadq-cai";
-// The expiration of issued verifiable credentials.
-const VC_EXPIRATION_PERIOD_NS: u64 = 15 * MINUTE_NS;
-// End of year 2024 as UNIX timestamp.
-const EOY_2024_TIMESTAMP_S: u32 = 1735685999;
+// Version of the `PreparedContext` format returned by `prepare_credential`.
+const PREPARED_CONTEXT_VERSION: u8 = 3;
+// Maximal number of prepared but not yet expired credential signatures per caller.
+const MAX_PENDING_SIGNATURES_PER_CALLER: usize = 10;
+// Maximal number of expired signatures removed from the signature map per call.
+const MAX_SIGS_TO_PRUNE: usize = 50;
+// Rate limit for `prepare_credential` if none is configured: bursts of 20 calls, 20 calls per minute.
+const DEFAULT_PREPARE_CREDENTIAL_RATE_LIMIT: RateLimitConfig = RateLimitConfig {
+    capacity: 20,
+    refill_interval_s: 3,
+};
+// Maximal number of events returned by `list_events_page`.
+const MAX_EVENTS_PER_PAGE: u32 = 100;
+// Maximal number of events inspected by a single `list_events_page` call.
+const MAX_EVENTS_TO_SCAN: usize = 10_000;
+// Maximal number of attendees returned by `list_event_attendees`.
+const MAX_ATTENDEES_PER_PAGE: u32 = 1_000;
+// Path of the metrics snapshot, in the Prometheus text format.
+const METRICS_PATH: &str = "/metrics";
+const METRICS_CONTENT_TYPE: &str = "text/plain; version=0.0.4";
+// Interval at which the `/metrics` snapshot is refreshed and re-certified, together with
+// the JSON documents served under `/api` if their data changed.
+const DOCUMENTS_REFRESH_INTERVAL: Duration = Duration::from_secs(60);
+// Interval at which the full `prepare_credential` rate limit buckets are removed.
+const RATE_LIMITS_PRUNE_INTERVAL: Duration = Duration::from_secs(60);
+// Version of the `StateChunk` format used by `export_state` and `import_state`.
+const STATE_CHUNK_FORMAT_VERSION: u32 = 1;
+// Maximal (approximate) size of the data of a single `StateChunk`.
+const MAX_STATE_CHUNK_BYTES: usize = 1_000_000;
+// Maximal number of attendees per `import_event_attendees` call.
+const MAX_IMPORTED_ATTENDEES: usize = 1_000;
+// Maximal number of deletion log entries returned by `list_deletions`.
+const MAX_DELETIONS_PER_PAGE: u64 = 100;
+// Maximal number of issuance log entries returned by `list_issuances`.
+const MAX_ISSUANCES_PER_PAGE: u32 = 100;
+// Maximal number of issuance log entries inspected by a single `list_issuances` call.
+const MAX_ISSUANCES_TO_SCAN: u64 = 10_000;
+// Maximal size of the argument of `prepare_credential` ingress messages.
+const MAX_PREPARE_CREDENTIAL_ARG_SIZE: usize = 16 * 1024;
+
+// Stored records are encoded as a version byte followed by the candid encoding of the record.
+// Records written before versioning was introduced are plain candid, which always starts
+// with the "DIDL" magic, and are treated as version 0.
+trait VersionedStorable: CandidType + for<'de> Deserialize<'de> {
+    const NAME: &'static str;
+    // Version of the current layout, must be below b'D' to be distinguishable from plain candid.
+    const VERSION: u8;
+
+    // Encoding of the record in the current layout, without the version byte.
+    // Candid by default, override it for records with a fixed layout.
+    fn encode_current(&self) -> Result<Vec<u8>, String> {
+        candid::encode_one(self).map_err(|e| e.to_string())
+    }
+
+    fn decode_current(bytes: &[u8]) -> Result<Self, String> {
+        candid::decode_one(bytes).map_err(|e| e.to_string())
+    }
+
+    // Decodes a record stored with an older layout. The default works as long as
+    // the layouts only differ by optional fields; override it when that's no longer the case.
+    fn decode_old_version(_version: u8, candid_bytes: &[u8]) -> Result<Self, String> {
+        candid::decode_one(candid_bytes).map_err(|e| e.to_string())
+    }
+}
+
+const CANDID_MAGIC: &[u8] = b"DIDL";
+
+fn encode_versioned<T: VersionedStorable>(record: &T) -> Vec<u8> {
+    let mut bytes = vec![T::VERSION];
+    bytes.extend(
+        record
+            .encode_current()
+            .unwrap_or_else(|e| panic!("failed to encode {}: {}", T::NAME, e)),
+    );
+    bytes
+}
+
+fn decode_versioned<T: VersionedStorable>(bytes: &[u8]) -> T {
+    let result = match bytes.split_first() {
+        _ if bytes.starts_with(CANDID_MAGIC) => T::decode_old_version(0, bytes),
+        Some((&version, bytes)) if version == T::VERSION => T::decode_current(bytes),
+        Some((&version, candid_bytes)) if version < T::VERSION => {
+            T::decode_old_version(version, candid_bytes)
+        }
+        Some((&version, _)) => Err(format!("unsupported version {}", version)),
+        None => Err("empty record".to_string()),
+    };
+    result.unwrap_or_else(|e| panic!("failed to decode {}: {}", T::NAME, e))
+}
 
 // Internal container of per-event data.
 #[derive(CandidType, Clone, Deserialize)]
@@ -58,14 +180,23 @@ struct EventRecord {
     // Users that want to register for an event need to pass the correct code.
     // The use case is that only users attending an event will learn about the code.
     pub registration_code: String,
+    // Optional, as events created before tags were introduced have no tags.
+    pub tags: Option<Vec<String>>,
+    // Archived events are hidden from the default listing, `None` means not archived.
+    pub archived: Option<bool>,
+}
+
+impl VersionedStorable for EventRecord {
+    const NAME: &'static str = "EventRecord";
+    const VERSION: u8 = 1;
 }
 
 impl Storable for EventRecord {
     fn to_bytes(&self) -> Cow<[u8]> {
-        Cow::Owned(candid::encode_one(self).expect("failed to encode EventRecord"))
+        Cow::Owned(encode_versioned(self))
     }
     fn from_bytes(bytes: Cow<[u8]>) -> Self {
-        candid::decode_one(&bytes).expect("failed to decode EventRecord")
+        decode_versioned(&bytes)
     }
     const BOUND: Bound = Bound::Unbounded;
 }
@@ -77,7 +208,89 @@ pub struct UserEventData {
     pub event_name: EventName,
 }
 
+// Stored per-user data, the events of the user are stored in `USER_EVENTS`.
+// Fixed layout: layout version (1 byte), joined_timestamp_s (4 bytes, big-endian).
+#[derive(CandidType, Clone, Copy, Deserialize)]
+struct EarlyAdopterRecord {
+    pub joined_timestamp_s: u32,
+}
+
+impl VersionedStorable for EarlyAdopterRecord {
+    const NAME: &'static str = "EarlyAdopterRecord";
+    const VERSION: u8 = 1;
+
+    fn encode_current(&self) -> Result<Vec<u8>, String> {
+        Ok(self.joined_timestamp_s.to_be_bytes().to_vec())
+    }
+
+    fn decode_current(bytes: &[u8]) -> Result<Self, String> {
+        let joined_timestamp_s = bytes
+            .try_into()
+            .map_err(|_| format!("unexpected length {}", bytes.len()))?;
+        Ok(Self {
+            joined_timestamp_s: u32::from_be_bytes(joined_timestamp_s),
+        })
+    }
+}
+
+impl Storable for EarlyAdopterRecord {
+    fn to_bytes(&self) -> Cow<[u8]> {
+        Cow::Owned(encode_versioned(self))
+    }
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        decode_versioned(&bytes)
+    }
+    const BOUND: Bound = Bound::Bounded {
+        max_size: 5,
+        is_fixed_size: true,
+    };
+}
+
+// Key of `USER_EVENTS`, ordered by principal first so that the events of a user are adjacent.
+// Fixed layout: principal length (1 byte), principal (padded to 29 bytes), event id (4 bytes, big-endian).
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+struct UserEventKey {
+    pub principal: Principal,
+    pub event_id: EventId,
+}
+
+impl UserEventKey {
+    // Range of the keys of all the events of the given user.
+    fn user_range(principal: Principal) -> std::ops::RangeInclusive<Self> {
+        Self {
+            principal,
+            event_id: EventId::MIN,
+        }..=Self {
+            principal,
+            event_id: EventId::MAX,
+        }
+    }
+}
+
+impl Storable for UserEventKey {
+    fn to_bytes(&self) -> Cow<[u8]> {
+        let principal = self.principal.as_slice();
+        let mut bytes = vec![0; 34];
+        bytes[0] = principal.len() as u8;
+        bytes[1..1 + principal.len()].copy_from_slice(principal);
+        bytes[30..].copy_from_slice(&self.event_id.to_be_bytes());
+        Cow::Owned(bytes)
+    }
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        let principal_len = bytes[0] as usize;
+        Self {
+            principal: Principal::from_slice(&bytes[1..1 + principal_len]),
+            event_id: EventId::from_be_bytes(bytes[30..34].try_into().unwrap()),
+        }
+    }
+    const BOUND: Bound = Bound::Bounded {
+        max_size: 34,
+        is_fixed_size: true,
+    };
+}
+
 // Internal container of per-user data.
+// Stored as is in `LEGACY_EARLY_ADOPTERS` before schema version 3.
 #[derive(CandidType, Clone, Deserialize)]
 struct EarlyAdopterData {
     pub joined_timestamp_s: u32,
@@ -90,12 +303,57 @@ struct UserEventRecord {
     pub joined_timestamp_s: u32,
 }
 
+impl Storable for UserEventRecord {
+    fn to_bytes(&self) -> Cow<[u8]> {
+        Cow::Owned(candid::encode_one(self).expect("failed to encode UserEventRecord"))
+    }
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        candid::decode_one(&bytes).expect("failed to decode UserEventRecord")
+    }
+    const BOUND: Bound = Bound::Unbounded;
+}
+
+// Key of the event attendees index, ordered by event first so that the attendees of an event are adjacent.
+// Fixed layout: event id (4 bytes, big-endian), principal length (1 byte), principal (padded to 29 bytes).
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+struct EventAttendeeKey {
+    pub event_id: EventId,
+    pub principal: Principal,
+}
+
+impl Storable for EventAttendeeKey {
+    fn to_bytes(&self) -> Cow<[u8]> {
+        let principal = self.principal.as_slice();
+        let mut bytes = vec![0; 34];
+        bytes[0..4].copy_from_slice(&self.event_id.to_be_bytes());
+        bytes[4] = principal.len() as u8;
+        bytes[5..5 + principal.len()].copy_from_slice(principal);
+        Cow::Owned(bytes)
+    }
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        let principal_len = bytes[4] as usize;
+        Self {
+            event_id: EventId::from_be_bytes(bytes[0..4].try_into().unwrap()),
+            principal: Principal::from_slice(&bytes[5..5 + principal_len]),
+        }
+    }
+    const BOUND: Bound = Bound::Bounded {
+        max_size: 34,
+        is_fixed_size: true,
+    };
+}
+
+impl VersionedStorable for EarlyAdopterData {
+    const NAME: &'static str = "EarlyAdopterData";
+    const VERSION: u8 = 1;
+}
+
 impl Storable for EarlyAdopterData {
     fn to_bytes(&self) -> Cow<[u8]> {
-        Cow::Owned(candid::encode_one(self).expect("failed to encode EarlyAdopterData"))
+        Cow::Owned(encode_versioned(self))
     }
     fn from_bytes(bytes: Cow<[u8]>) -> Self {
-        candid::decode_one(&bytes).expect("failed to decode EarlyAdopterData")
+        decode_versioned(&bytes)
     }
     const BOUND: Bound = Bound::Unbounded;
 }
@@ -107,6 +365,23 @@ pub struct EarlyAdopterResponse {
     pub events: Vec<UserEventData>,
 }
 
+impl From<EarlyAdopterData> for EarlyAdopterResponse {
+    fn from(data: EarlyAdopterData) -> Self {
+        let events: Vec<UserEventData> = data
+            .events
+            .into_iter()
+            .map(|(event_name, event)| UserEventData {
+                joined_timestamp_s: event.joined_timestamp_s,
+                event_name,
+            })
+            .collect();
+        Self {
+            joined_timestamp_s: data.joined_timestamp_s,
+            events,
+        }
+    }
+}
+
 #[derive(CandidType, Deserialize)]
 pub enum RegisterError {
     Internal(String),
@@ -131,6 +406,7 @@ pub struct RegisterUserRequest {
 pub struct AddEventRequest {
     pub event_name: EventName,
     pub registration_code: Option<String>,
+    pub tags: Option<Vec<String>>,
 }
 
 // User-facing type used in add_event
@@ -147,6 +423,8 @@ pub struct EventData {
     pub event_name: EventName,
     pub registration_code: Option<String>,
     pub created_timestamp_s: u32,
+    pub tags: Vec<String>,
+    pub archived: bool,
 }
 
 // User-facing type used in list_events
@@ -155,6 +433,273 @@ pub struct ListEventsResponse {
     pub events: Vec<EventData>,
 }
 
+// User-facing type used in list_events_page
+#[derive(CandidType, Clone, Deserialize)]
+pub struct ListEventsPageRequest {
+    // Only events with a name strictly greater than `start_after` are returned.
+    pub start_after: Option<EventName>,
+    pub max_results: Option<u32>,
+    pub created_after_s: Option<u32>,
+    pub created_before_s: Option<u32>,
+    pub tag: Option<String>,
+    // If not set, only events that are not archived are returned.
+    pub archived: Option<bool>,
+}
+
+// User-facing type used in list_events_page
+#[derive(CandidType, Clone, Deserialize)]
+pub struct ListEventsPageResponse {
+    pub events: Vec<EventData>,
+    // Cursor to pass as `start_after` to get the next page, if not all events were inspected.
+    // As at most `MAX_EVENTS_TO_SCAN` events are inspected per call, a page may contain
+    // fewer than `max_results` events even if there are more matching events.
+    pub next_start_after: Option<EventName>,
+}
+
+// User-facing type used in list_event_attendees
+#[derive(CandidType, Clone, Deserialize)]
+pub struct ListEventAttendeesRequest {
+    pub event_name: EventName,
+    // Only attendees with a principal strictly greater than `start_after` are returned.
+    pub start_after: Option<Principal>,
+    pub max_results: Option<u32>,
+}
+
+// User-facing type used in ListEventAttendeesResponse
+#[derive(CandidType, Clone, Deserialize)]
+pub struct EventAttendeeData {
+    pub principal: Principal,
+    pub joined_timestamp_s: u32,
+}
+
+// User-facing type used in list_event_attendees
+#[derive(CandidType, Clone, Deserialize)]
+pub struct ListEventAttendeesResponse {
+    pub attendees: Vec<EventAttendeeData>,
+    // Total number of attendees of the event.
+    pub total_count: u64,
+    // Cursor to pass as `start_after` to get the next page, if there are more attendees.
+    pub next_start_after: Option<Principal>,
+}
+
+// User-facing type used in ImportEventAttendeesRequest
+#[derive(CandidType, Clone, Deserialize)]
+pub struct ImportedAttendee {
+    pub principal: Principal,
+    // Time of the check-in, the time of the import if not set.
+    pub joined_timestamp_s: Option<u32>,
+}
+
+// User-facing type used in import_event_attendees
+#[derive(CandidType, Clone, Deserialize)]
+pub struct ImportEventAttendeesRequest {
+    pub event_name: EventName,
+    pub attendees: Vec<ImportedAttendee>,
+}
+
+// User-facing type used in ImportEventAttendeesResponse
+#[derive(CandidType, Clone, Deserialize)]
+pub enum ImportAttendeeResult {
+    Registered,
+    AlreadyRegistered,
+    Rejected(String),
+}
+
+// User-facing type used in import_event_attendees
+#[derive(CandidType, Clone, Deserialize)]
+pub struct ImportEventAttendeesResponse {
+    // One result per attendee in the request, in the same order.
+    pub results: Vec<(Principal, ImportAttendeeResult)>,
+}
+
+// Position in the exported state, used to request the next chunk of export_state.
+#[derive(CandidType, Clone, Deserialize)]
+pub enum ExportCursor {
+    // Events with a name strictly greater than the given one.
+    Events(Option<EventName>),
+    // Early adopters with a principal strictly greater than the given one.
+    EarlyAdopters(Option<Principal>),
+}
+
+// User-facing type used in export_state and import_state.
+#[derive(CandidType, Clone, Deserialize)]
+pub struct StateChunk {
+    pub format_version: u32,
+    // Candid encoding of `StateChunkData`.
+    pub data: ByteBuf,
+    // SHA-256 of `data`.
+    pub checksum: ByteBuf,
+    // Cursor to pass to export_state to get the next chunk, `None` for the last chunk.
+    pub next: Option<ExportCursor>,
+}
+
+// Content of a `StateChunk`.
+#[derive(CandidType, Deserialize)]
+struct StateChunkData {
+    pub events: Vec<(EventName, EventRecord)>,
+    pub early_adopters: Vec<(Principal, EarlyAdopterData)>,
+}
+
+// User-facing type used in import_state
+#[derive(CandidType, Clone, Deserialize)]
+pub struct ImportStateResponse {
+    pub events_imported: u64,
+    pub early_adopters_imported: u64,
+}
+
+// User-facing type used in archive_event
+#[derive(CandidType, Clone, Deserialize)]
+pub struct ArchiveEventRequest {
+    pub event_name: EventName,
+    pub archived: bool,
+}
+
+// Internal record of a prepared credential, appended to the issuance log.
+// Contains no personal data: the principals are kept in `ISSUANCE_SUBJECTS`,
+// from which they are removed when the user deletes their data.
+#[derive(CandidType, Clone, Deserialize)]
+struct IssuanceRecord {
+    pub timestamp_ns: u64,
+    pub idp_canister_id: Principal,
+    pub credential_type: String,
+    // Arguments of the credential spec, sorted by name.
+    pub arguments: Vec<(String, ArgumentValue)>,
+}
+
+impl Storable for IssuanceRecord {
+    fn to_bytes(&self) -> Cow<[u8]> {
+        Cow::Owned(candid::encode_one(self).expect("failed to encode IssuanceRecord"))
+    }
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        candid::decode_one(&bytes).expect("failed to decode IssuanceRecord")
+    }
+    const BOUND: Bound = Bound::Unbounded;
+}
+
+// Internal record of the user a credential was prepared for, by issuance log index.
+#[derive(CandidType, Clone, Deserialize)]
+struct IssuanceSubject {
+    pub id_dapp: Principal,
+    pub id_alias: Principal,
+}
+
+impl Storable for IssuanceSubject {
+    fn to_bytes(&self) -> Cow<[u8]> {
+        Cow::Owned(candid::encode_one(self).expect("failed to encode IssuanceSubject"))
+    }
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        candid::decode_one(&bytes).expect("failed to decode IssuanceSubject")
+    }
+    const BOUND: Bound = Bound::Unbounded;
+}
+
+// Key of `USER_ISSUANCES`, ordered by principal first so that the issuances of a user are adjacent.
+// Fixed layout: principal length (1 byte), principal (padded to 29 bytes), log index (8 bytes, big-endian).
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+struct UserIssuanceKey {
+    pub principal: Principal,
+    pub index: u64,
+}
+
+impl UserIssuanceKey {
+    // Range of the keys of all the issuances of the given user.
+    fn user_range(principal: Principal) -> std::ops::RangeInclusive<Self> {
+        Self {
+            principal,
+            index: u64::MIN,
+        }..=Self {
+            principal,
+            index: u64::MAX,
+        }
+    }
+}
+
+impl Storable for UserIssuanceKey {
+    fn to_bytes(&self) -> Cow<[u8]> {
+        let principal = self.principal.as_slice();
+        let mut bytes = vec![0; 38];
+        bytes[0] = principal.len() as u8;
+        bytes[1..1 + principal.len()].copy_from_slice(principal);
+        bytes[30..].copy_from_slice(&self.index.to_be_bytes());
+        Cow::Owned(bytes)
+    }
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        let principal_len = bytes[0] as usize;
+        Self {
+            principal: Principal::from_slice(&bytes[1..1 + principal_len]),
+            index: u64::from_be_bytes(bytes[30..38].try_into().unwrap()),
+        }
+    }
+    const BOUND: Bound = Bound::Bounded {
+        max_size: 38,
+        is_fixed_size: true,
+    };
+}
+
+// Internal record of a deleted user, appended to the deletion log.
+// Contains no personal data of the deleted user.
+#[derive(CandidType, Clone, Deserialize)]
+struct DeletionRecord {
+    pub timestamp_ns: u64,
+    // Whether the deletion was requested by an admin (or by the user themselves).
+    pub deleted_by_admin: bool,
+    pub events_count: u32,
+}
+
+impl Storable for DeletionRecord {
+    fn to_bytes(&self) -> Cow<[u8]> {
+        Cow::Owned(candid::encode_one(self).expect("failed to encode DeletionRecord"))
+    }
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        candid::decode_one(&bytes).expect("failed to decode DeletionRecord")
+    }
+    const BOUND: Bound = Bound::Unbounded;
+}
+
+// User-facing type used in list_deletions
+#[derive(CandidType, Clone, Deserialize)]
+pub struct DeletionData {
+    pub index: u64,
+    pub timestamp_ns: u64,
+    pub deleted_by_admin: bool,
+    pub events_count: u32,
+}
+
+// User-facing type used in ListIssuancesResponse
+#[derive(CandidType, Clone, Deserialize)]
+pub struct IssuanceData {
+    pub index: u64,
+    pub timestamp_ns: u64,
+    // `None` if the user deleted their data.
+    pub id_dapp: Option<Principal>,
+    pub id_alias: Option<Principal>,
+    pub idp_canister_id: Principal,
+    pub credential_type: String,
+    pub arguments: Vec<(String, ArgumentValue)>,
+}
+
+// User-facing type used in list_issuances
+#[derive(CandidType, Clone, Deserialize)]
+pub struct ListIssuancesRequest {
+    pub id_dapp: Option<Principal>,
+    pub credential_type: Option<String>,
+    pub from_timestamp_ns: Option<u64>,
+    pub to_timestamp_ns: Option<u64>,
+    // Index of the first log entry to consider, 0 if not set.
+    pub start_index: Option<u64>,
+    pub max_results: Option<u32>,
+}
+
+// User-facing type used in list_issuances
+#[derive(CandidType, Clone, Deserialize)]
+pub struct ListIssuancesResponse {
+    pub issuances: Vec<IssuanceData>,
+    // Total number of entries in the issuance log.
+    pub total_count: u64,
+    // Index to continue the listing from, if the end of the log was not reached.
+    pub next_index: Option<u64>,
+}
+
 thread_local! {
     /// Stable structures
     // Static configuration of the canister set by init() or post_upgrade().
@@ -163,24 +708,114 @@ thread_local! {
     static MEMORY_MANAGER: RefCell<MemoryManager<Memory>> =
         RefCell::new(MemoryManager::init(managed_memory()));
 
+    // Per-user data before schema version 3, only read by migrations.
+    static LEGACY_EARLY_ADOPTERS : RefCell<LegacyEarlyAdoptersMap> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(LEGACY_EARLY_ADOPTERS_MEMORY_ID)),
+    ));
+
     static EARLY_ADOPTERS : RefCell<EarlyAdoptersMap> = RefCell::new(
         StableBTreeMap::init(
             MEMORY_MANAGER.with(|m| m.borrow().get(EARLY_ADOPTERS_MEMORY_ID)),
     ));
 
+    // Events of each user, with the timestamp at which the user joined the event.
+    static USER_EVENTS : RefCell<UserEventsMap> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(USER_EVENTS_MEMORY_ID)),
+    ));
+
+    // Numeric ids of the events, so that user records don't repeat the event names.
+    static EVENT_IDS : RefCell<EventIdsMap> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(EVENT_IDS_MEMORY_ID)),
+    ));
+
+    static EVENT_NAMES : RefCell<EventNamesMap> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(EVENT_NAMES_MEMORY_ID)),
+    ));
+
     static EVENTS : RefCell<EventsMap> = RefCell::new(
         StableBTreeMap::init(
               MEMORY_MANAGER.with(|m| m.borrow().get(EVENTS_MEMORY_ID)),
     ));
 
+    // Secondary index of EARLY_ADOPTERS: attendees of each event.
+    static EVENT_ATTENDEES : RefCell<EventAttendeesMap> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(EVENT_ATTENDEES_MEMORY_ID)),
+    ));
+
+    // Number of entries of each event in EVENT_ATTENDEES, events without attendees are omitted.
+    static EVENT_ATTENDEE_COUNTS : RefCell<EventAttendeeCountsMap> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(EVENT_ATTENDEE_COUNTS_MEMORY_ID)),
+    ));
+
+    static ISSUANCE_LOG : RefCell<IssuanceLog> = RefCell::new(
+        StableLog::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(ISSUANCE_LOG_INDEX_MEMORY_ID)),
+            MEMORY_MANAGER.with(|m| m.borrow().get(ISSUANCE_LOG_DATA_MEMORY_ID)),
+    ).expect("failed to initialize issuance log"));
+
+    // Principals of the ISSUANCE_LOG entries, by log index.
+    static ISSUANCE_SUBJECTS : RefCell<IssuanceSubjectsMap> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(ISSUANCE_SUBJECTS_MEMORY_ID)),
+    ));
+
+    // Secondary index of ISSUANCE_SUBJECTS: log indices of the issuances of each id_dapp.
+    static USER_ISSUANCES : RefCell<UserIssuancesMap> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(USER_ISSUANCES_MEMORY_ID)),
+    ));
+
+    static DELETION_LOG : RefCell<DeletionLog> = RefCell::new(
+        StableLog::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(DELETION_LOG_INDEX_MEMORY_ID)),
+            MEMORY_MANAGER.with(|m| m.borrow().get(DELETION_LOG_DATA_MEMORY_ID)),
+    ).expect("failed to initialize deletion log"));
+
+    // Version of the stable memory layout, see `MIGRATIONS`.
+    // Canisters installed before the version was tracked have version 0.
+    static SCHEMA_VERSION : RefCell<SchemaVersionCell> = RefCell::new(
+        StableCell::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(SCHEMA_VERSION_MEMORY_ID)),
+            0,
+    ).expect("failed to initialize schema version cell"));
+
     /// Non-stable structures
     // Canister signatures
     static SIGNATURES : RefCell<SignatureMap> = RefCell::new(SignatureMap::default());
+    // Message hashes of the signatures prepared by each caller. A signature is pending as long
+    // as it is held by `SIGNATURES`, see `pending_signatures_count`.
+    static PENDING_SIGNATURES: RefCell<BTreeMap<Principal, Vec<Hash>>> = RefCell::new(BTreeMap::new());
+    // Hashes of the contexts handed out by `prepare_credential`, mapped to the message hash of
+    // their signature. Kept as long as the signature is held by `SIGNATURES`.
+    static PREPARED_CONTEXTS: RefCell<BTreeMap<Hash, Hash>> = RefCell::new(BTreeMap::new());
+    // Token buckets for rate limiting `prepare_credential`, see `RateLimitConfig`.
+    static RATE_LIMITS: RefCell<BTreeMap<Principal, TokenBucket>> = RefCell::new(BTreeMap::new());
+    // Generator of opaque credential ids, initialized on first use.
+    static CREDENTIAL_ID_GENERATOR: RefCell<Option<CredentialIdGenerator>> = RefCell::new(None);
+    // Time used by `CanisterClock` instead of the replica time, see `set_time`.
+    #[cfg(feature = "test-clock")]
+    static TIME_OVERRIDE_NS: RefCell<Option<u64>> = RefCell::new(None);
+    // Counters exported as metrics, reset on upgrade.
+    static COUNTERS: RefCell<Counters> = RefCell::new(Counters::default());
+    // Number of registered users by the hour (since the epoch) in which they joined, for the
+    // hours of the last day. Rebuilt from `EARLY_ADOPTERS` on upgrade.
+    static RECENT_REGISTRATIONS: RefCell<BTreeMap<u32, u64>> = RefCell::new(BTreeMap::new());
     // Assets for the management app
-    static ASSETS: RefCell<CertifiedAssets> = RefCell::new(CertifiedAssets::default());
-
-
-
+    static ASSETS: RefCell<CertifiedHttpAssets> = RefCell::new(CertifiedHttpAssets::default());
+    // Whether the data shown in `API_DOCUMENTS` and `BADGES` changed since they were built.
+    static DOCUMENTS_STALE: RefCell<bool> = RefCell::new(false);
+    // JSON documents served under `/api` and `/.well-known` by URL path, as included in `ASSETS`.
+    static API_DOCUMENTS: RefCell<BTreeMap<String, Vec<u8>>> = RefCell::new(BTreeMap::new());
+    // SVG badges served at `/badge/{event}.svg` by URL path, as included in `ASSETS`.
+    static BADGES: RefCell<BTreeMap<String, Vec<u8>>> = RefCell::new(BTreeMap::new());
+    // Snapshot of the metrics served at `/metrics`, as included in `ASSETS`.
+    static METRICS_SNAPSHOT: RefCell<Vec<u8>> = RefCell::new(vec![]);
 }
 
 lazy_static! {
@@ -205,7 +840,7 @@ fn managed_memory() -> Memory {
 #[cfg(target_arch = "wasm32")]
 use ic_cdk::println;
 
-#[derive(CandidType, Deserialize)]
+#[derive(CandidType, Clone, Deserialize)]
 struct IssuerConfig {
     /// Root of trust for checking canister signatures.
     ic_root_key_raw: Vec<u8>,
@@ -215,14 +850,43 @@ struct IssuerConfig {
     derivation_origin: String,
     /// Frontend hostname to be used by the issuer.
     frontend_hostname: String,
+    /// Rate limit for `prepare_credential` calls per principal.
+    /// If not set, `DEFAULT_PREPARE_CREDENTIAL_RATE_LIMIT` applies.
+    prepare_credential_rate_limit: Option<RateLimitConfig>,
+    /// URL identifying the issuer in the credentials, `DEFAULT_ISSUER_URL` if not set.
+    issuer_url: Option<String>,
+    /// How the ids of the credentials are built, `CredentialIdScheme::DataUrl` if not set.
+    credential_id_scheme: Option<CredentialIdScheme>,
+}
+
+/// Scheme of the ids (`jti`) of the issued credentials.
+#[derive(CandidType, Clone, Copy, Deserialize, PartialEq)]
+enum CredentialIdScheme {
+    /// `data:` URL with the issuer URL, the timestamp and the subject of the credential.
+    DataUrl,
+    /// Random `urn:uuid:` URN, which cannot be correlated with the subject.
+    Opaque,
+}
+
+/// Token bucket configuration: every principal can make up to `capacity` calls in a burst,
+/// and gets one call back every `refill_interval_s` seconds.
+#[derive(CandidType, Clone, Deserialize)]
+struct RateLimitConfig {
+    capacity: u32,
+    refill_interval_s: u32,
+}
+
+impl VersionedStorable for IssuerConfig {
+    const NAME: &'static str = "IssuerConfig";
+    const VERSION: u8 = 1;
 }
 
 impl Storable for IssuerConfig {
     fn to_bytes(&self) -> Cow<[u8]> {
-        Cow::Owned(candid::encode_one(self).expect("failed to encode IssuerConfig"))
+        Cow::Owned(encode_versioned(self))
     }
     fn from_bytes(bytes: Cow<[u8]>) -> Self {
-        candid::decode_one(&bytes).expect("failed to decode IssuerConfig")
+        decode_versioned(&bytes)
     }
     const BOUND: Bound = Bound::Unbounded;
 }
@@ -236,6 +900,9 @@ impl Default for IssuerConfig {
             idp_canister_ids: vec![Principal::from_text(PROD_II_CANISTER_ID).unwrap()],
             derivation_origin: derivation_origin.clone(),
             frontend_hostname: derivation_origin, // by default, use DERIVATION_ORIGIN as frontend-hostname
+            prepare_credential_rate_limit: None,
+            issuer_url: None,
+            credential_id_scheme: None,
         }
     }
 }
@@ -248,6 +915,9 @@ impl From<IssuerInit> for IssuerConfig {
             idp_canister_ids: init.idp_canister_ids,
             derivation_origin: init.derivation_origin,
             frontend_hostname: init.frontend_hostname,
+            prepare_credential_rate_limit: init.prepare_credential_rate_limit,
+            issuer_url: init.issuer_url,
+            credential_id_scheme: init.credential_id_scheme,
         }
     }
 }
@@ -262,59 +932,442 @@ struct IssuerInit {
     derivation_origin: String,
     /// Frontend hostname to be used by the issuer.
     frontend_hostname: String,
+    /// Rate limit for `prepare_credential` calls per principal.
+    prepare_credential_rate_limit: Option<RateLimitConfig>,
+    /// URL identifying the issuer in the credentials.
+    issuer_url: Option<String>,
+    /// How the ids of the credentials are built.
+    credential_id_scheme: Option<CredentialIdScheme>,
 }
 
 #[init]
 #[candid_method(init)]
 fn init(init_arg: Option<IssuerInit>) {
+    // A freshly installed canister starts with the current layout.
+    // On upgrade, the migrations have already been run at this point.
+    set_schema_version(CURRENT_SCHEMA_VERSION);
     if let Some(init) = init_arg {
+        if let Err(err) = check_config(&init) {
+            ic_cdk::trap(&err);
+        }
         apply_config(init);
     };
 
     init_assets();
+    ic_cdk_timers::set_timer_interval(RATE_LIMITS_PRUNE_INTERVAL, prune_rate_limits);
 }
 
 #[post_upgrade]
 fn post_upgrade(init_arg: Option<IssuerInit>) {
+    migrate_stable_memory();
     init(init_arg);
+    rebuild_recent_registrations();
 }
 
-// TODO: restrict or remove `configure()`.
-#[update]
-#[candid_method]
-fn configure(config: IssuerInit) {
-    apply_config(config);
+// Migrations of the stable memory layout: `MIGRATIONS[i]` migrates from version `i` to `i + 1`.
+// Migrations must be idempotent, as an upgrade that traps rolls back the version as well.
+const MIGRATIONS: &[fn()] = &[
+    backfill_event_attendees,
+    reencode_versioned_records,
+    compact_early_adopters,
+    count_event_attendees,
+];
+const CURRENT_SCHEMA_VERSION: u32 = MIGRATIONS.len() as u32;
+
+fn migrate_stable_memory() {
+    let mut version = SCHEMA_VERSION.with_borrow(|cell| *cell.get());
+    if version > CURRENT_SCHEMA_VERSION {
+        ic_cdk::trap(&format!(
+            "Stable memory schema version {} is newer than the supported version {}",
+            version, CURRENT_SCHEMA_VERSION
+        ));
+    }
+    while version < CURRENT_SCHEMA_VERSION {
+        MIGRATIONS[version as usize]();
+        version += 1;
+        set_schema_version(version);
+        println!("Migrated stable memory to schema version {}.", version);
+    }
 }
 
-fn apply_config(init: IssuerInit) {
-    CONFIG
-        .with_borrow_mut(|config_cell| config_cell.set(IssuerConfig::from(init)))
-        .expect("failed to apply issuer config");
+fn set_schema_version(version: u32) {
+    SCHEMA_VERSION.with_borrow_mut(|cell| {
+        cell.set(version)
+            .expect("failed to set stable memory schema version")
+    });
 }
 
-fn authorize_vc_request(
-    alias: &SignedIdAlias,
-    expected_vc_subject: &Principal,
-    current_time_ns: u128,
-) -> Result<AliasTuple, IssueCredentialError> {
-    CONFIG.with_borrow(|config| {
-        let config = config.get();
+// Rewrites the records stored as plain candid with the versioned encoding.
+fn reencode_versioned_records() {
+    EVENTS.with_borrow_mut(|events| {
+        let entries: Vec<_> = events.iter().collect();
+        for (event_name, event) in entries {
+            events.insert(event_name, event);
+        }
+    });
+    LEGACY_EARLY_ADOPTERS.with_borrow_mut(|adopters| {
+        let entries: Vec<_> = adopters.iter().collect();
+        for (principal, data) in entries {
+            adopters.insert(principal, data);
+        }
+    });
+    CONFIG.with_borrow_mut(|config_cell| {
+        let config = config_cell.get().clone();
+        config_cell.set(config).expect("failed to re-encode config");
+    });
+}
 
-        for idp_canister_id in &config.idp_canister_ids {
-            if let Ok(alias_tuple) = get_verified_id_alias_from_jws(
-                &alias.credential_jws,
-                expected_vc_subject,
-                idp_canister_id,
-                &config.ic_root_key_raw,
-                current_time_ns,
-            ) {
-                return Ok(alias_tuple);
-            }
+// Assigns ids to the existing events, in the order of their names.
+fn assign_event_ids() {
+    let event_names: Vec<EventName> =
+        EVENTS.with_borrow(|events| events.iter().map(|(event_name, _)| event_name).collect());
+    for event_name in event_names {
+        event_id_or_assign(&event_name);
+    }
+}
+
+// Moves the per-user data from `LEGACY_EARLY_ADOPTERS` to `EARLY_ADOPTERS` and `USER_EVENTS`,
+// leaving the legacy map empty.
+// The memory manager cannot release the buckets of a virtual memory, so memory id 0 stays
+// allocated to the (empty) legacy map and must not be reused for other data.
+fn compact_early_adopters() {
+    assign_event_ids();
+    let entries: Vec<_> = LEGACY_EARLY_ADOPTERS.with_borrow(|adopters| adopters.iter().collect());
+    for (principal, data) in &entries {
+        store_early_adopter(*principal, data);
+    }
+    LEGACY_EARLY_ADOPTERS.with_borrow_mut(|adopters| {
+        for (principal, _) in entries {
+            adopters.remove(&principal);
         }
-        Err(IssueCredentialError::InvalidIdAlias(
-            "id alias could not be verified".to_string(),
-        ))
-    })
+    });
+}
+
+#[update]
+#[candid_method]
+async fn configure(config: IssuerInit) -> Result<(), RegisterError> {
+    if !is_admin(caller()).await {
+        return Err(RegisterError::External(
+            "Only controllers can configure the issuer".to_string(),
+        ));
+    }
+    check_config(&config).map_err(RegisterError::External)?;
+    apply_config(config);
+    // The issuer URL appears in the certified documents.
+    rebuild_certified_documents();
+    Ok(())
+}
+
+fn issuer_url() -> String {
+    CONFIG.with_borrow(|config| {
+        config
+            .get()
+            .issuer_url
+            .clone()
+            .unwrap_or_else(|| DEFAULT_ISSUER_URL.to_string())
+    })
+}
+
+fn credential_id_scheme() -> CredentialIdScheme {
+    CONFIG.with_borrow(|config| {
+        config
+            .get()
+            .credential_id_scheme
+            .unwrap_or(CredentialIdScheme::DataUrl)
+    })
+}
+
+// Checks the config for the values that cannot be used, returning the reason.
+fn check_config(init: &IssuerInit) -> Result<(), String> {
+    match &init.issuer_url {
+        Some(issuer_url) if !issuer_url.starts_with("https://") => {
+            Err("issuer_url must be an https URL".to_string())
+        }
+        _ => Ok(()),
+    }
+}
+
+fn apply_config(init: IssuerInit) {
+    CONFIG
+        .with_borrow_mut(|config_cell| config_cell.set(IssuerConfig::from(init)))
+        .expect("failed to apply issuer config");
+}
+
+#[inspect_message]
+fn inspect_message() {
+    match method_name().as_str() {
+        "prepare_credential" => {
+            if caller() == Principal::anonymous() {
+                ic_cdk::trap("prepare_credential is not available for the anonymous principal");
+            }
+            if arg_data_raw_size() > MAX_PREPARE_CREDENTIAL_ARG_SIZE {
+                ic_cdk::trap("prepare_credential argument is too large");
+            }
+            // Traps if the argument cannot be decoded.
+            let (req,): (PrepareCredentialRequest,) = arg_data();
+            if req.signed_id_alias.credential_jws.split('.').count() != 3 {
+                ic_cdk::trap("malformed id alias credential");
+            }
+        }
+        _ => {}
+    }
+    accept_message();
+}
+
+// Internal state of the rate limit of a single principal.
+struct TokenBucket {
+    tokens: u32,
+    last_refill_ns: u64,
+}
+
+impl TokenBucket {
+    // Whether the bucket would be full after refilling, i.e. it does not need to be stored.
+    fn is_full(&self, config: &RateLimitConfig, now_ns: u64) -> bool {
+        now_ns.saturating_sub(self.last_refill_ns) / config.refill_interval_ns()
+            + (self.tokens as u64)
+            >= config.capacity as u64
+    }
+}
+
+impl RateLimitConfig {
+    fn refill_interval_ns(&self) -> u64 {
+        (self.refill_interval_s.max(1) as u64) * 1_000_000_000
+    }
+}
+
+fn prepare_credential_rate_limit() -> RateLimitConfig {
+    CONFIG.with_borrow(|config| {
+        config
+            .get()
+            .prepare_credential_rate_limit
+            .clone()
+            .unwrap_or(DEFAULT_PREPARE_CREDENTIAL_RATE_LIMIT)
+    })
+}
+
+// Takes a token from the bucket of the given principal, fails if the bucket is empty.
+fn check_rate_limit(principal: Principal, now_ns: u64) -> Result<(), IssueCredentialError> {
+    let config = prepare_credential_rate_limit();
+    let refill_interval_ns = config.refill_interval_ns();
+    RATE_LIMITS.with_borrow_mut(|buckets| {
+        let bucket = buckets.entry(principal).or_insert(TokenBucket {
+            tokens: config.capacity,
+            last_refill_ns: now_ns,
+        });
+        let refills = now_ns.saturating_sub(bucket.last_refill_ns) / refill_interval_ns;
+        if refills > 0 {
+            bucket.tokens = (bucket.tokens as u64 + refills).min(config.capacity as u64) as u32;
+            bucket.last_refill_ns += refills * refill_interval_ns;
+        }
+        if bucket.tokens == 0 {
+            return Err(ThrottlingError::RateLimitExceeded {
+                retry_after_s: (bucket.last_refill_ns + refill_interval_ns - now_ns)
+                    .div_ceil(1_000_000_000),
+            }
+            .into());
+        }
+        bucket.tokens -= 1;
+        Ok(())
+    })
+}
+
+// Removes the buckets that are full again, run periodically by a timer.
+fn prune_rate_limits() {
+    let config = prepare_credential_rate_limit();
+    let now_ns = time();
+    RATE_LIMITS
+        .with_borrow_mut(|buckets| buckets.retain(|_, bucket| !bucket.is_full(&config, now_ns)));
+}
+
+// Returns the verified alias tuple and the identity provider that issued the alias.
+fn authorize_vc_request(
+    alias: &SignedIdAlias,
+    expected_vc_subject: &Principal,
+    current_time_ns: u128,
+) -> Result<(AliasTuple, Principal), IssueCredentialError> {
+    CONFIG.with_borrow(|config| {
+        verify_id_alias(
+            config.get(),
+            &alias.credential_jws,
+            expected_vc_subject,
+            current_time_ns,
+        )
+        .map_err(|cause| {
+            COUNTERS.with_borrow_mut(|counters| {
+                *counters
+                    .id_alias_verification_failures
+                    .entry(cause.label())
+                    .or_default() += 1
+            });
+            println!(
+                "*** id alias for {} could not be verified: {:?}",
+                expected_vc_subject.to_text(),
+                cause
+            );
+            IssueCredentialError::InvalidIdAlias(format!(
+                "id alias could not be verified ({}): {}",
+                cause.label(),
+                cause
+            ))
+        })
+    })
+}
+
+// Verifies the id alias JWS wrt. the identity provider that signed it, which must be one of
+// the configured identity providers. The canister signature is verified only once, and the
+// claims are then checked on the verified result.
+fn verify_id_alias(
+    config: &IssuerConfig,
+    credential_jws: &str,
+    expected_vc_subject: &Principal,
+    current_time_ns: u128,
+) -> Result<(AliasTuple, Principal), IdAliasError> {
+    let (signer, unverified_claims) = decode_id_alias_jws(credential_jws)?;
+    if !config.idp_canister_ids.contains(&signer) {
+        return Err(IdAliasError::UnknownIdp { signer });
+    }
+    // Checked before the signature, so that expired credentials are rejected as such, and
+    // without verifying their canister signature.
+    let now_s = (current_time_ns / 1_000_000_000) as i64;
+    match unverified_claims
+        .get("exp")
+        .and_then(serde_json::Value::as_i64)
+    {
+        Some(exp_s) if exp_s > now_s => {}
+        exp_s => return Err(IdAliasError::Expired { exp_s, now_s }),
+    }
+
+    let claims = verify_credential_jws_with_canister_id(
+        credential_jws,
+        &signer,
+        &config.ic_root_key_raw,
+        current_time_ns,
+    )
+    .map_err(IdAliasError::from)?;
+    if claims.sub() != Some(did_for_principal(*expected_vc_subject).as_str()) {
+        return Err(IdAliasError::WrongSubject {
+            expected: *expected_vc_subject,
+            actual: claims.sub().map(str::to_string),
+        });
+    }
+    let id_alias = claims
+        .vc()
+        .filter(|vc| {
+            vc.get("type")
+                .and_then(serde_json::Value::as_array)
+                .map_or(false, |types| {
+                    types.iter().any(|t| t == "InternetIdentityIdAlias")
+                })
+        })
+        .and_then(|vc| vc.get("credentialSubject"))
+        .and_then(|subject| subject.pointer("/InternetIdentityIdAlias/hasIdAlias"))
+        .and_then(serde_json::Value::as_str)
+        .and_then(|id_alias| Principal::from_text(id_alias).ok())
+        .ok_or(IdAliasError::InvalidClaims)?;
+    Ok((
+        AliasTuple {
+            id_alias,
+            id_dapp: *expected_vc_subject,
+        },
+        signer,
+    ))
+}
+
+/// Reason why an id alias credential was rejected by `verify_id_alias`.
+#[derive(Debug)]
+enum IdAliasError {
+    /// The JWS could not be decoded, or does not carry a canister signature public key.
+    Malformed,
+    /// The JWS was signed by a canister that is not in `idp_canister_ids`.
+    UnknownIdp { signer: Principal },
+    /// The canister signature of the JWS is not valid wrt. the configured root key.
+    InvalidSignature,
+    /// The id alias credential expired, or does not have an expiration date.
+    Expired { exp_s: Option<i64>, now_s: i64 },
+    /// The id alias credential was issued for a different principal than the caller.
+    WrongSubject {
+        expected: Principal,
+        actual: Option<String>,
+    },
+    /// The signed claims are not those of an id alias credential.
+    InvalidClaims,
+}
+
+impl From<JwtValidationError> for IdAliasError {
+    fn from(err: JwtValidationError) -> Self {
+        match err {
+            JwtValidationError::Signature { .. } => IdAliasError::InvalidSignature,
+            JwtValidationError::JwsDecodingError(_)
+            | JwtValidationError::MethodDataLookupError { .. } => IdAliasError::Malformed,
+            _ => IdAliasError::InvalidClaims,
+        }
+    }
+}
+
+impl IdAliasError {
+    fn label(&self) -> &'static str {
+        match self {
+            IdAliasError::Malformed => "malformed",
+            IdAliasError::UnknownIdp { .. } => "unknown idp",
+            IdAliasError::InvalidSignature => "invalid signature",
+            IdAliasError::Expired { .. } => "expired",
+            IdAliasError::WrongSubject { .. } => "wrong subject",
+            IdAliasError::InvalidClaims => "invalid claims",
+        }
+    }
+}
+
+impl std::fmt::Display for IdAliasError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            IdAliasError::Malformed => write!(f, "malformed JWS"),
+            IdAliasError::UnknownIdp { signer } => {
+                write!(f, "unknown identity provider {}", signer)
+            }
+            IdAliasError::InvalidSignature => write!(f, "signature could not be verified"),
+            IdAliasError::Expired {
+                exp_s: Some(exp_s),
+                now_s,
+            } => write!(f, "credential expired at {}, current time {}", exp_s, now_s),
+            IdAliasError::Expired { exp_s: None, .. } => {
+                write!(f, "credential has no expiration date")
+            }
+            IdAliasError::WrongSubject { expected, actual } => write!(
+                f,
+                "expected subject {}, got {}",
+                did_for_principal(*expected),
+                actual.as_deref().unwrap_or("none")
+            ),
+            IdAliasError::InvalidClaims => write!(f, "not an id alias credential"),
+        }
+    }
+}
+
+// Returns the canister that signed the JWS, according to the canister signature public key in
+// its header, and the claims of the JWS. Neither is verified, the signature is checked by
+// `verify_credential_jws_with_canister_id`.
+fn decode_id_alias_jws(
+    credential_jws: &str,
+) -> Result<(Principal, serde_json::Value), IdAliasError> {
+    let mut parts = credential_jws.split('.');
+    let mut decode_part = || {
+        parts
+            .next()
+            .and_then(|part| URL_SAFE_NO_PAD.decode(part).ok())
+            .and_then(|bytes| serde_json::from_slice::<serde_json::Value>(&bytes).ok())
+            .ok_or(IdAliasError::Malformed)
+    };
+    let header = decode_part()?;
+    let claims = decode_part()?;
+    let public_key_der = header
+        .pointer("/jwk/k")
+        .and_then(|k| k.as_str())
+        .and_then(|k| URL_SAFE_NO_PAD.decode(k).ok())
+        .ok_or(IdAliasError::Malformed)?;
+    let signer = CanisterSigPublicKey::try_from(public_key_der.as_slice())
+        .map(|public_key| public_key.canister_id)
+        .map_err(|_| IdAliasError::Malformed)?;
+    Ok((signer, claims))
 }
 
 #[update]
@@ -322,10 +1375,99 @@ fn authorize_vc_request(
 async fn prepare_credential(
     req: PrepareCredentialRequest,
 ) -> Result<PreparedCredentialData, IssueCredentialError> {
-    let alias_tuple = match authorize_vc_request(&req.signed_id_alias, &caller(), time().into()) {
-        Ok(alias_tuple) => alias_tuple,
-        Err(err) => return Err(err),
-    };
+    let credential_type = req.credential_spec.credential_type.clone();
+    let result = prepare_and_sign_credential(req).await;
+    COUNTERS.with_borrow_mut(|counters| match &result {
+        Ok(_) => {
+            *counters
+                .prepared_credentials
+                .entry(credential_type)
+                .or_default() += 1
+        }
+        Err(err) => {
+            *counters
+                .prepare_credential_failures
+                .entry(issue_credential_error_label(err))
+                .or_default() += 1
+        }
+    });
+    result
+}
+
+// Generator of opaque credential ids: UUIDs derived from a random seed and a counter.
+struct CredentialIdGenerator {
+    seed: Vec<u8>,
+    counter: u64,
+}
+
+impl CredentialIdGenerator {
+    fn next_id(&mut self) -> String {
+        let mut hasher = Sha256::new();
+        hasher.update(&self.seed);
+        hasher.update(self.counter.to_be_bytes());
+        self.counter += 1;
+        let mut bytes: [u8; 32] = hasher.finalize().into();
+        // Version 4 (random) UUID, RFC 4122 variant.
+        bytes[6] = (bytes[6] & 0x0f) | 0x40;
+        bytes[8] = (bytes[8] & 0x3f) | 0x80;
+        let hex = hex::encode(&bytes[..16]);
+        format!(
+            "urn:uuid:{}-{}-{}-{}-{}",
+            &hex[0..8],
+            &hex[8..12],
+            &hex[12..16],
+            &hex[16..20],
+            &hex[20..32]
+        )
+    }
+}
+
+// Obtains the seed of `CREDENTIAL_ID_GENERATOR` from the management canister,
+// if opaque credential ids are configured and the seed is not there yet.
+async fn init_credential_id_generator() -> Result<(), IssueCredentialError> {
+    if credential_id_scheme() != CredentialIdScheme::Opaque
+        || CREDENTIAL_ID_GENERATOR.with_borrow(|generator| generator.is_some())
+    {
+        return Ok(());
+    }
+    let (seed,) = raw_rand()
+        .await
+        .map_err(|(_, msg)| internal_error(&format!("failed to obtain randomness: {}", msg)))?;
+    CREDENTIAL_ID_GENERATOR.with_borrow_mut(|generator| {
+        generator.get_or_insert(CredentialIdGenerator { seed, counter: 0 });
+    });
+    Ok(())
+}
+
+// Rejects the caller if it has too many prepared credentials that were not fetched yet.
+fn check_pending_signatures(caller: Principal) -> Result<(), IssueCredentialError> {
+    prune_expired_signatures(time());
+    if pending_signatures_count(caller) >= MAX_PENDING_SIGNATURES_PER_CALLER {
+        return Err(ThrottlingError::TooManyPendingCredentials {
+            max: MAX_PENDING_SIGNATURES_PER_CALLER,
+        }
+        .into());
+    }
+    Ok(())
+}
+
+async fn prepare_and_sign_credential(
+    req: PrepareCredentialRequest,
+) -> Result<PreparedCredentialData, IssueCredentialError> {
+    check_rate_limit(caller(), time())?;
+    let (alias_tuple, idp_canister_id) =
+        match authorize_vc_request(&req.signed_id_alias, &caller(), time().into()) {
+            Ok(verified_alias) => verified_alias,
+            Err(err) => return Err(err),
+        };
+
+    check_pending_signatures(caller())?;
+    // Only authorized and not throttled callers get to make the issuer call the management canister.
+    init_credential_id_generator().await?;
+    // Other calls of the caller may have prepared credentials while awaiting the randomness.
+    check_pending_signatures(caller())?;
+
+    let now_ns = time();
 
     let credential_jwt = match prepare_credential_jwt(&req.credential_spec, &alias_tuple) {
         Ok(credential) => credential,
@@ -339,12 +1481,209 @@ async fn prepare_credential(
         let mut sigs = sigs.borrow_mut();
         sigs.add_signature(&CANISTER_SIG_SEED, msg_hash);
     });
+    PENDING_SIGNATURES.with_borrow_mut(|pending| {
+        let msg_hashes = pending.entry(caller()).or_default();
+        if !msg_hashes.contains(&msg_hash) {
+            msg_hashes.push(msg_hash);
+        }
+    });
     update_root_hash();
+    append_issuance_record(
+        IssuanceRecord {
+            timestamp_ns: now_ns,
+            idp_canister_id,
+            credential_type: req.credential_spec.credential_type.clone(),
+            arguments: req
+                .credential_spec
+                .arguments
+                .iter()
+                .flatten()
+                .map(|(name, value)| (name.clone(), value.clone()))
+                .collect::<BTreeMap<_, _>>()
+                .into_iter()
+                .collect(),
+        },
+        IssuanceSubject {
+            id_dapp: alias_tuple.id_dapp,
+            id_alias: alias_tuple.id_alias,
+        },
+    );
+    let prepared_context = PreparedContext::new(req.credential_spec, credential_jwt).encode();
+    PREPARED_CONTEXTS
+        .with_borrow_mut(|contexts| contexts.insert(hash_bytes(&prepared_context), msg_hash));
     Ok(PreparedCredentialData {
-        prepared_context: Some(ByteBuf::from(credential_jwt.as_bytes())),
+        prepared_context: Some(ByteBuf::from(prepared_context)),
+    })
+}
+
+/// Context handed out by `prepare_credential` and passed back to `get_credential`.
+/// The issuer keeps the hash of every context it hands out (see `PREPARED_CONTEXTS`), so
+/// `get_credential` only accepts contexts prepared by the issuer, for the caller's id alias
+/// and the requested credential spec.
+#[derive(CandidType, Deserialize)]
+struct PreparedContext {
+    version: u8,
+    credential_spec: CredentialSpec,
+    credential_jwt: String,
+}
+
+/// Reason why a prepared context was rejected by `PreparedContext::decode_and_verify`.
+#[derive(Debug)]
+enum PreparedContextError {
+    /// The context could not be decoded.
+    Malformed(String),
+    /// The context was prepared by a release using a different format.
+    UnsupportedVersion(u8),
+    /// The context was not prepared by the issuer, or its signature expired.
+    Unknown,
+}
+
+impl std::fmt::Display for PreparedContextError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PreparedContextError::Malformed(msg) => write!(f, "malformed: {}", msg),
+            PreparedContextError::UnsupportedVersion(version) => {
+                write!(f, "unsupported version {}", version)
+            }
+            PreparedContextError::Unknown => write!(f, "not prepared or expired"),
+        }
+    }
+}
+
+impl From<PreparedContextError> for IssueCredentialError {
+    fn from(err: PreparedContextError) -> Self {
+        IssueCredentialError::SignatureNotFound(format!("invalid prepared_context: {}", err))
+    }
+}
+
+impl PreparedContext {
+    fn new(credential_spec: CredentialSpec, credential_jwt: String) -> Self {
+        Self {
+            version: PREPARED_CONTEXT_VERSION,
+            credential_spec,
+            credential_jwt,
+        }
+    }
+
+    fn encode(&self) -> Vec<u8> {
+        candid::encode_one(self).expect("failed to encode PreparedContext")
+    }
+
+    // Decodes the context, checks that it was prepared by the issuer, and that it is a
+    // credential for the given id alias and credential spec.
+    fn decode_and_verify(
+        bytes: &[u8],
+        credential_spec: &CredentialSpec,
+        id_alias: &Principal,
+    ) -> Result<Self, IssueCredentialError> {
+        let context: PreparedContext = candid::decode_one(bytes)
+            .map_err(|e| PreparedContextError::Malformed(e.to_string()))?;
+        if context.version != PREPARED_CONTEXT_VERSION {
+            return Err(PreparedContextError::UnsupportedVersion(context.version).into());
+        }
+        // The hash is looked up for the exact bytes that were handed out, and must belong to
+        // the signature of the contained JWT.
+        let signing_input = vc_signing_input(&context.credential_jwt, &CANISTER_SIG_PK)
+            .map_err(|e| PreparedContextError::Malformed(format!("{:?}", e)))?;
+        let msg_hash = vc_signing_input_hash(&signing_input);
+        if PREPARED_CONTEXTS.with_borrow(|contexts| contexts.get(&hash_bytes(bytes)).copied())
+            != Some(msg_hash)
+        {
+            return Err(PreparedContextError::Unknown.into());
+        }
+        if context.credential_spec.credential_type != credential_spec.credential_type
+            || context.credential_spec.arguments != credential_spec.arguments
+        {
+            return Err(IssueCredentialError::UnsupportedCredentialSpec(
+                "credential spec does not match the prepared credential".to_string(),
+            ));
+        }
+        let claims: serde_json::Value = serde_json::from_str(&context.credential_jwt)
+            .map_err(|e| PreparedContextError::Malformed(e.to_string()))?;
+        if claims.get("sub").and_then(|sub| sub.as_str())
+            != Some(did_for_principal(*id_alias).as_str())
+        {
+            return Err(IssueCredentialError::UnauthorizedSubject(format!(
+                "credential was not prepared for id alias {}",
+                id_alias.to_text()
+            )));
+        }
+        Ok(context)
+    }
+}
+
+// Removes expired signatures from the signature map, and re-certifies the signatures
+// if any of them were removed.
+fn prune_expired_signatures(now_ns: u64) {
+    let pruned = SIGNATURES.with_borrow_mut(|sigs| sigs.prune_expired(now_ns, MAX_SIGS_TO_PRUNE));
+    if pruned == 0 {
+        return;
+    }
+    PENDING_SIGNATURES.with_borrow_mut(|pending| {
+        pending.retain(|_, msg_hashes| {
+            msg_hashes.retain(|msg_hash| is_signature_pending(msg_hash));
+            !msg_hashes.is_empty()
+        })
+    });
+    PREPARED_CONTEXTS
+        .with_borrow_mut(|contexts| contexts.retain(|_, msg_hash| is_signature_pending(msg_hash)));
+    update_root_hash();
+}
+
+// Returns the number of signatures prepared by the given caller that are still held by the
+// signature map.
+fn pending_signatures_count(principal: Principal) -> usize {
+    PENDING_SIGNATURES.with_borrow(|pending| {
+        pending
+            .get(&principal)
+            .map(|msg_hashes| {
+                msg_hashes
+                    .iter()
+                    .filter(|msg_hash| is_signature_pending(msg_hash))
+                    .count()
+            })
+            .unwrap_or_default()
+    })
+}
+
+fn is_signature_pending(msg_hash: &Hash) -> bool {
+    SIGNATURES.with_borrow(|sigs| {
+        sigs.witness(hash_bytes(CANISTER_SIG_SEED.as_slice()), *msg_hash)
+            .is_some()
     })
 }
 
+fn append_issuance_record(record: IssuanceRecord, subject: IssuanceSubject) {
+    let index = ISSUANCE_LOG.with_borrow(|log| {
+        log.append(&record)
+            .expect("failed to append to issuance log")
+    });
+    USER_ISSUANCES.with_borrow_mut(|user_issuances| {
+        user_issuances.insert(
+            UserIssuanceKey {
+                principal: subject.id_dapp,
+                index,
+            },
+            (),
+        )
+    });
+    ISSUANCE_SUBJECTS.with_borrow_mut(|subjects| subjects.insert(index, subject));
+}
+
+// Removes the principals of the user from the issuance log entries of the user's credentials.
+fn remove_issuance_subjects(id_dapp: Principal) {
+    let keys: Vec<UserIssuanceKey> = USER_ISSUANCES.with_borrow(|user_issuances| {
+        user_issuances
+            .range(UserIssuanceKey::user_range(id_dapp))
+            .map(|(key, _)| key)
+            .collect()
+    });
+    for key in keys {
+        ISSUANCE_SUBJECTS.with_borrow_mut(|subjects| subjects.remove(&key.index));
+        USER_ISSUANCES.with_borrow_mut(|user_issuances| user_issuances.remove(&key));
+    }
+}
+
 fn update_root_hash() {
     SIGNATURES.with_borrow(|sigs| {
         ASSETS.with_borrow(|assets| {
@@ -362,8 +1701,9 @@ fn update_root_hash() {
 #[query]
 #[candid_method(query)]
 fn get_credential(req: GetCredentialRequest) -> Result<IssuedCredentialData, IssueCredentialError> {
-    if let Err(err) = authorize_vc_request(&req.signed_id_alias, &caller(), time().into()) {
-        return Result::<IssuedCredentialData, IssueCredentialError>::Err(err);
+    let alias_tuple = match authorize_vc_request(&req.signed_id_alias, &caller(), time().into()) {
+        Ok((alias_tuple, _)) => alias_tuple,
+        Err(err) => return Result::<IssuedCredentialData, IssueCredentialError>::Err(err),
     };
     if let Err(err) = verify_credential_spec(&req.credential_spec) {
         return Result::<IssuedCredentialData, IssueCredentialError>::Err(err);
@@ -371,18 +1711,18 @@ fn get_credential(req: GetCredentialRequest) -> Result<IssuedCredentialData, Iss
     let prepared_context = match req.prepared_context {
         Some(context) => context,
         None => {
-            return Result::<IssuedCredentialData, IssueCredentialError>::Err(internal_error(
-                "missing prepared_context",
-            ))
+            return Result::<IssuedCredentialData, IssueCredentialError>::Err(
+                PreparedContextError::Malformed("missing".to_string()).into(),
+            )
         }
     };
-    let credential_jwt = match String::from_utf8(prepared_context.into_vec()) {
-        Ok(s) => s,
-        Err(_) => {
-            return Result::<IssuedCredentialData, IssueCredentialError>::Err(internal_error(
-                "invalid prepared_context",
-            ))
-        }
+    let credential_jwt = match PreparedContext::decode_and_verify(
+        &prepared_context,
+        &req.credential_spec,
+        &alias_tuple.id_alias,
+    ) {
+        Ok(context) => context.credential_jwt,
+        Err(err) => return Result::<IssuedCredentialData, IssueCredentialError>::Err(err),
     };
     let signing_input =
         vc_signing_input(&credential_jwt, &CANISTER_SIG_PK).expect("failed getting signing_input");
@@ -412,6 +1752,57 @@ fn get_credential(req: GetCredentialRequest) -> Result<IssuedCredentialData, Iss
     Result::<IssuedCredentialData, IssueCredentialError>::Ok(IssuedCredentialData { vc_jws })
 }
 
+/// Presentation of a credential issued by this issuer, as received by a relying party.
+#[derive(CandidType, Deserialize)]
+struct VerifyPresentationRequest {
+    /// Id alias credential (JWS) issued by one of the `idp_canister_ids`.
+    id_alias_jws: String,
+    /// Credential (JWS) issued by this issuer for the id alias.
+    vc_jws: String,
+    /// The credential spec the relying party asked for.
+    credential_spec: CredentialSpec,
+    /// Principal of the user at the relying party.
+    id_dapp: Principal,
+}
+
+#[derive(CandidType, Debug)]
+enum VerifyPresentationError {
+    /// The id alias credential is not valid for `id_dapp`.
+    InvalidIdAlias(String),
+    /// The credential was not issued by this issuer for the id alias and the credential spec.
+    InvalidCredential(String),
+}
+
+/// Verifies a presentation on behalf of canister-based relying parties: the id alias must be
+/// signed by one of the identity providers for `id_dapp`, and the credential must be signed
+/// by this issuer for the id alias, not expired, and match the credential spec.
+#[query]
+#[candid_method(query)]
+fn verify_presentation(req: VerifyPresentationRequest) -> Result<(), VerifyPresentationError> {
+    let current_time_ns = time() as u128;
+    let (alias_tuple, _) = CONFIG
+        .with_borrow(|config| {
+            verify_id_alias(
+                config.get(),
+                &req.id_alias_jws,
+                &req.id_dapp,
+                current_time_ns,
+            )
+        })
+        .map_err(|cause| VerifyPresentationError::InvalidIdAlias(cause.to_string()))?;
+    let ic_root_key_raw = CONFIG.with_borrow(|config| config.get().ic_root_key_raw.clone());
+    verify_credential_for_subject(
+        &req.vc_jws,
+        &ic_root_key_raw,
+        ic_cdk::id(),
+        &issuer_url(),
+        &req.credential_spec,
+        alias_tuple.id_alias,
+        current_time_ns,
+    )
+    .map_err(|err| VerifyPresentationError::InvalidCredential(err.to_string()))
+}
+
 #[update]
 #[candid_method]
 async fn vc_consent_message(
@@ -443,98 +1834,6 @@ fn get_derivation_origin(hostname: &str) -> Result<DerivationOriginData, Derivat
     })
 }
 
-pub fn get_vc_consent_message_en(
-    credential_spec: &CredentialSpec,
-) -> Result<Icrc21ConsentInfo, Icrc21Error> {
-    match verify_credential_spec(credential_spec) {
-        Ok(SupportedCredentialType::EarlyAdopter(since_year)) => Ok(Icrc21ConsentInfo {
-            consent_message: format!("You became an early adopter in {}.", since_year),
-            language: "en".to_string(),
-        }),
-        Ok(SupportedCredentialType::EventAttendance(event_name)) => Ok(Icrc21ConsentInfo {
-            consent_message: format!("You have attended the event {}.", event_name),
-            language: "en".to_string(),
-        }),
-        Err(err) => Err(Icrc21Error::ConsentMessageUnavailable(Icrc21ErrorInfo {
-            description: format!("Credential spec not supported: {:?}", err),
-        })),
-    }
-}
-
-// Note: not very accurate, but we cannot depend on `chrono` due to WASM-ability.
-// Assumption: `year` is at least 2024 (as checked before calling this function)
-fn year_to_max_timestamp_s(year: i32) -> u32 {
-    const SECONDS_IN_YEAR: u32 = 31_536_000;
-    EOY_2024_TIMESTAMP_S + ((year - 2024) as u32) * SECONDS_IN_YEAR
-}
-
-fn verify_early_adopter_spec_and_get_since_year(spec: &CredentialSpec) -> Result<i32, String> {
-    if spec.credential_type.as_str() == "EarlyAdopter" {
-        let Some(arguments) = &spec.arguments else {
-            return Err("Credential spec has no arguments".to_string());
-        };
-        let expected_argument = "sinceYear";
-        let Some(value) = arguments.get(expected_argument) else {
-            return Err(format!(
-                "Credential spec has no {}-argument",
-                expected_argument
-            ));
-        };
-        if arguments.len() != 1 {
-            return Err("Credential spec has unexpected arguments".to_string());
-        }
-        let ArgumentValue::Int(year) = value else {
-            return Err(format!(
-                "Credential spec has unexpected value for {}-argument",
-                expected_argument
-            ));
-        };
-        if *year < 2024 {
-            return Err(format!(
-                "Credential spec has unsupported value for {}-argument",
-                expected_argument
-            ));
-        };
-
-        Ok(*year)
-    } else {
-        Err(format!(
-            "Credential {} is not supported",
-            spec.credential_type.as_str()
-        ))
-    }
-}
-
-fn verify_event_attendance_and_get_event_name(spec: &CredentialSpec) -> Result<&String, String> {
-    if spec.credential_type.as_str() == "EventAttendance" {
-        let Some(arguments) = &spec.arguments else {
-            return Err("Credential spec has no arguments".to_string());
-        };
-        let expected_argument = "eventName";
-        let Some(value) = arguments.get(expected_argument) else {
-            return Err(format!(
-                "Credential spec has no {}-argument",
-                expected_argument
-            ));
-        };
-        if arguments.len() != 1 {
-            return Err("Credential spec has unexpected arguments".to_string());
-        }
-        let ArgumentValue::String(event_name) = value else {
-            return Err(format!(
-                "Credential spec has unexpected value for {}-argument",
-                expected_argument
-            ));
-        };
-        Ok(event_name)
-    } else {
-        Err(format!(
-            "Credential {} is not supported",
-            spec.credential_type.as_str()
-        ))
-    }
-}
-
 // TODO: Create and manage admins instead of using canister controllers.
 async fn is_admin(id: Principal) -> bool {
     is_controller(&id)
@@ -548,20 +1847,168 @@ async fn list_events() -> Result<ListEventsResponse, RegisterError> {
     EVENTS.with_borrow(|events| {
         let events: Vec<EventData> = events
             .iter()
-            .map(|(event_name, data)| EventData {
-                created_timestamp_s: data.created_timestamp_s.clone(),
-                event_name: event_name.clone(),
-                registration_code: if is_admin {
-                    Some(data.registration_code)
-                } else {
-                    None
-                },
-            })
+            .map(|(event_name, data)| event_data(event_name, data, is_admin))
             .collect();
         Ok(ListEventsResponse { events })
     })
 }
 
+#[query]
+#[candid_method(query)]
+async fn list_events_page(
+    request: ListEventsPageRequest,
+) -> Result<ListEventsPageResponse, RegisterError> {
+    let is_admin = is_admin(caller()).await;
+    // An empty page would not advance the cursor, so at least one event is returned.
+    let max_results = request
+        .max_results
+        .unwrap_or(MAX_EVENTS_PER_PAGE)
+        .clamp(1, MAX_EVENTS_PER_PAGE) as usize;
+    let start = match request.start_after.clone() {
+        Some(start_after) => RangeBound::Excluded(start_after),
+        None => RangeBound::Unbounded,
+    };
+    EVENTS.with_borrow(|events| {
+        let mut page = vec![];
+        let mut next_start_after = None;
+        let mut last_scanned = None;
+        for (scanned, (event_name, data)) in
+            events.range((start, RangeBound::Unbounded)).enumerate()
+        {
+            if page.len() == max_results || scanned == MAX_EVENTS_TO_SCAN {
+                next_start_after = last_scanned;
+                break;
+            }
+            if event_matches(&data, &request) {
+                page.push(event_data(event_name.clone(), data, is_admin));
+            }
+            last_scanned = Some(event_name);
+        }
+        Ok(ListEventsPageResponse {
+            events: page,
+            next_start_after,
+        })
+    })
+}
+
+fn event_matches(event: &EventRecord, request: &ListEventsPageRequest) -> bool {
+    request
+        .created_after_s
+        .map_or(true, |after| event.created_timestamp_s >= after)
+        && request
+            .created_before_s
+            .map_or(true, |before| event.created_timestamp_s < before)
+        && request.tag.as_ref().map_or(true, |tag| {
+            event.tags.as_ref().map_or(false, |tags| tags.contains(tag))
+        })
+        && request.archived.unwrap_or(false) == event.archived.unwrap_or(false)
+}
+
+fn event_data(event_name: EventName, event: EventRecord, is_admin: bool) -> EventData {
+    EventData {
+        created_timestamp_s: event.created_timestamp_s,
+        event_name,
+        registration_code: if is_admin {
+            Some(event.registration_code)
+        } else {
+            None
+        },
+        tags: event.tags.unwrap_or_default(),
+        archived: event.archived.unwrap_or(false),
+    }
+}
+
+#[update]
+#[candid_method]
+async fn archive_event(request: ArchiveEventRequest) -> Result<EventData, RegisterError> {
+    if !is_admin(caller()).await {
+        return Err(RegisterError::External(
+            "Only controllers can archive events".to_string(),
+        ));
+    }
+    let Some(mut event_record) = get_event(request.event_name.clone()) else {
+        return Err(RegisterError::External(format!(
+            "Event {} does not exist",
+            request.event_name
+        )));
+    };
+    event_record.archived = Some(request.archived);
+    EVENTS.with_borrow_mut(|events| {
+        events.insert(request.event_name.clone(), event_record.clone());
+    });
+    mark_documents_stale();
+    Ok(event_data(request.event_name, event_record, true))
+}
+
+#[query]
+#[candid_method(query)]
+async fn list_issuances(
+    request: ListIssuancesRequest,
+) -> Result<ListIssuancesResponse, RegisterError> {
+    if !is_admin(caller()).await {
+        return Err(RegisterError::External(
+            "Only controllers can list issuances".to_string(),
+        ));
+    }
+    let max_results = request
+        .max_results
+        .unwrap_or(MAX_ISSUANCES_PER_PAGE)
+        .min(MAX_ISSUANCES_PER_PAGE) as usize;
+    ISSUANCE_LOG.with_borrow(|log| {
+        let total_count = log.len();
+        let start_index = request.start_index.unwrap_or(0);
+        let end_index = total_count.min(start_index.saturating_add(MAX_ISSUANCES_TO_SCAN));
+        let mut issuances = vec![];
+        let mut index = start_index;
+        while index < end_index && issuances.len() < max_results {
+            let record = log.get(index).expect("missing issuance log entry");
+            let subject = ISSUANCE_SUBJECTS.with_borrow(|subjects| subjects.get(&index));
+            if issuance_matches(&record, subject.as_ref(), &request) {
+                issuances.push(IssuanceData {
+                    index,
+                    timestamp_ns: record.timestamp_ns,
+                    id_dapp: subject.as_ref().map(|subject| subject.id_dapp),
+                    id_alias: subject.map(|subject| subject.id_alias),
+                    idp_canister_id: record.idp_canister_id,
+                    credential_type: record.credential_type,
+                    arguments: record.arguments,
+                });
+            }
+            index += 1;
+        }
+        Ok(ListIssuancesResponse {
+            issuances,
+            total_count,
+            next_index: if index < total_count {
+                Some(index)
+            } else {
+                None
+            },
+        })
+    })
+}
+
+fn issuance_matches(
+    record: &IssuanceRecord,
+    subject: Option<&IssuanceSubject>,
+    request: &ListIssuancesRequest,
+) -> bool {
+    request.id_dapp.map_or(true, |id_dapp| {
+        subject.map_or(false, |subject| subject.id_dapp == id_dapp)
+    }) && request
+        .credential_type
+        .as_ref()
+        .map_or(true, |credential_type| {
+            &record.credential_type == credential_type
+        })
+        && request
+            .from_timestamp_ns
+            .map_or(true, |from| record.timestamp_ns >= from)
+        && request
+            .to_timestamp_ns
+            .map_or(true, |to| record.timestamp_ns < to)
+}
+
 // Returns a random string of length 24 formed of lower case letters.
 // The code will be used to register users in a specific event.
 // See `code` in `EventRecord` for more info.
@@ -589,7 +2036,7 @@ async fn generate_randome_event_code() -> Option<String> {
 #[candid_method]
 async fn add_event(request: AddEventRequest) -> Result<AddEventResponse, RegisterError> {
     let user_id = caller();
-    let now_s = (time() / 1_000_000_000) as u32;
+    let now_s = now_s();
     // Exit early if the event_name is present by is empty.
     if request.event_name.clone().is_empty() {
         return Err(RegisterError::External(
@@ -619,9 +2066,13 @@ async fn add_event(request: AddEventRequest) -> Result<AddEventResponse, Registe
             let new_event = EventRecord {
                 created_timestamp_s: now_s,
                 registration_code: registration_code.clone(),
+                tags: request.tags.clone(),
+                archived: None,
             };
             events.insert(request.event_name.clone(), new_event);
         });
+        event_id_or_assign(&request.event_name);
+        mark_documents_stale();
         println!(
             "Registered Event {} at timestamp {}.",
             request.event_name, now_s
@@ -638,102 +2089,765 @@ async fn add_event(request: AddEventRequest) -> Result<AddEventResponse, Registe
     }
 }
 
-fn get_event(event_name: String) -> Option<EventRecord> {
-    EVENTS.with_borrow(|events| {
-        if let Some(event_record) = events.get(&event_name) {
-            Some(event_record)
-        } else {
-            None
-        }
+fn event_id(event_name: &str) -> Option<EventId> {
+    // `StableBTreeMap::get` needs the owned key type.
+    EVENT_IDS.with_borrow(|event_ids| event_ids.get(&event_name.to_string()))
+}
+
+// Returns the id of the event, assigning the next free id if the event has none yet.
+fn event_id_or_assign(event_name: &EventName) -> EventId {
+    if let Some(event_id) = event_id(event_name) {
+        return event_id;
+    }
+    let event_id = EVENT_NAMES.with_borrow_mut(|event_names| {
+        let event_id = event_names
+            .last_key_value()
+            .map_or(0, |(last_id, _)| last_id + 1);
+        event_names.insert(event_id, event_name.clone());
+        event_id
+    });
+    EVENT_IDS.with_borrow_mut(|event_ids| event_ids.insert(event_name.clone(), event_id));
+    event_id
+}
+
+// Assembles the data of the user from `EARLY_ADOPTERS` and `USER_EVENTS`.
+fn load_early_adopter(user_id: Principal) -> Option<EarlyAdopterData> {
+    let record = EARLY_ADOPTERS.with_borrow(|adopters| adopters.get(&user_id))?;
+    Some(early_adopter_data(user_id, record))
+}
+
+fn early_adopter_data(user_id: Principal, record: EarlyAdopterRecord) -> EarlyAdopterData {
+    let events = USER_EVENTS.with_borrow(|user_events| {
+        EVENT_NAMES.with_borrow(|event_names| {
+            user_events
+                .range(UserEventKey::user_range(user_id))
+                .map(|(key, joined_timestamp_s)| {
+                    let event_name = event_names
+                        .get(&key.event_id)
+                        .expect("user event without event name");
+                    (event_name, UserEventRecord { joined_timestamp_s })
+                })
+                .collect()
+        })
+    });
+    EarlyAdopterData {
+        joined_timestamp_s: record.joined_timestamp_s,
+        events,
+    }
+}
+
+// Stores the data of the user in `EARLY_ADOPTERS` and `USER_EVENTS`, replacing any existing data.
+fn store_early_adopter(user_id: Principal, data: &EarlyAdopterData) {
+    EARLY_ADOPTERS.with_borrow_mut(|adopters| {
+        adopters.insert(
+            user_id,
+            EarlyAdopterRecord {
+                joined_timestamp_s: data.joined_timestamp_s,
+            },
+        )
+    });
+    remove_user_events(user_id);
+    for (event_name, event) in &data.events {
+        let key = UserEventKey {
+            principal: user_id,
+            event_id: event_id_or_assign(event_name),
+        };
+        USER_EVENTS
+            .with_borrow_mut(|user_events| user_events.insert(key, event.joined_timestamp_s));
+    }
+}
+
+fn remove_user_events(user_id: Principal) {
+    USER_EVENTS.with_borrow_mut(|user_events| {
+        let keys: Vec<UserEventKey> = user_events
+            .range(UserEventKey::user_range(user_id))
+            .map(|(key, _)| key)
+            .collect();
+        for key in keys {
+            user_events.remove(&key);
+        }
+    });
+}
+
+fn get_event(event_name: String) -> Option<EventRecord> {
+    EVENTS.with_borrow(|events| {
+        if let Some(event_record) = events.get(&event_name) {
+            Some(event_record)
+        } else {
+            None
+        }
+    })
+}
+
+#[update]
+#[candid_method]
+fn register_early_adopter(
+    request: RegisterUserRequest,
+) -> Result<EarlyAdopterResponse, RegisterError> {
+    let user_id = caller();
+    let now_s = now_s();
+    // Validate event name and code (if present)
+    if let Some(requested_event) = request.event_data.clone() {
+        // Exit early if the event_name is present by is empty.
+        if requested_event.event_name.clone().is_empty() {
+            return Err(RegisterError::External(
+                "event_name cannot be an empty string if present".to_string(),
+            ));
+        }
+        // Exit early if the event doesn't exist.
+        let Some(event_record) = get_event(requested_event.event_name.clone()) else {
+            return Err(RegisterError::Internal(format!(
+                "Event {} does not exist",
+                requested_event.event_name
+            )));
+        };
+        // Exit early if the passed code doesn't match the event code
+        if event_record.registration_code != requested_event.registration_code {
+            return Err(RegisterError::Internal(format!(
+                "Registration code doesn't match for Event {}",
+                requested_event.event_name
+            )));
+        }
+    }
+    // At this point, the event is present and the code is valid.
+    let current_data = insert_early_adopter(
+        user_id,
+        request
+            .event_data
+            .map(|event_data| (event_data.event_name, now_s)),
+        now_s,
+    );
+    println!(
+        "Registered User {} at timestamp {}.",
+        user_id.to_text(),
+        current_data.joined_timestamp_s
+    );
+    Ok(EarlyAdopterResponse::from(current_data))
+}
+
+// Returns the registration of the caller, or `None` if the caller is not registered.
+// Unlike `register_early_adopter`, this never registers the caller.
+#[query]
+#[candid_method(query)]
+fn get_my_registration() -> Option<EarlyAdopterResponse> {
+    load_early_adopter(caller()).map(EarlyAdopterResponse::from)
+}
+
+// Registers attendees that checked in offline for an event.
+// Attendees that are already registered for the event are left unchanged.
+#[update]
+#[candid_method]
+async fn import_event_attendees(
+    request: ImportEventAttendeesRequest,
+) -> Result<ImportEventAttendeesResponse, RegisterError> {
+    if !is_admin(caller()).await {
+        return Err(RegisterError::External(
+            "Only controllers can import attendees".to_string(),
+        ));
+    }
+    let Some(event) = get_event(request.event_name.clone()) else {
+        return Err(RegisterError::External(format!(
+            "Event {} does not exist",
+            request.event_name
+        )));
+    };
+    if request.attendees.len() > MAX_IMPORTED_ATTENDEES {
+        return Err(RegisterError::External(format!(
+            "Too many attendees, at most {} can be imported at once",
+            MAX_IMPORTED_ATTENDEES
+        )));
+    }
+    let now_s = now_s();
+    let results = request
+        .attendees
+        .into_iter()
+        .map(|attendee| {
+            let result = import_event_attendee(&request.event_name, &event, &attendee, now_s);
+            (attendee.principal, result)
+        })
+        .collect();
+    Ok(ImportEventAttendeesResponse { results })
+}
+
+fn import_event_attendee(
+    event_name: &EventName,
+    event: &EventRecord,
+    attendee: &ImportedAttendee,
+    now_s: u32,
+) -> ImportAttendeeResult {
+    if attendee.principal == Principal::anonymous() {
+        return ImportAttendeeResult::Rejected(
+            "the anonymous principal cannot be registered".to_string(),
+        );
+    }
+    let joined_timestamp_s = attendee.joined_timestamp_s.unwrap_or(now_s);
+    if joined_timestamp_s > now_s {
+        return ImportAttendeeResult::Rejected(format!(
+            "joined_timestamp_s {} is in the future",
+            joined_timestamp_s
+        ));
+    }
+    if joined_timestamp_s < event.created_timestamp_s {
+        return ImportAttendeeResult::Rejected(format!(
+            "joined_timestamp_s {} is before the event was created",
+            joined_timestamp_s
+        ));
+    }
+    let event_id = event_id_or_assign(event_name);
+    let already_registered = USER_EVENTS.with_borrow(|user_events| {
+        user_events.contains_key(&UserEventKey {
+            principal: attendee.principal,
+            event_id,
+        })
+    });
+    if already_registered {
+        return ImportAttendeeResult::AlreadyRegistered;
+    }
+    insert_early_adopter(
+        attendee.principal,
+        Some((event_name.clone(), joined_timestamp_s)),
+        now_s,
+    );
+    ImportAttendeeResult::Registered
+}
+
+// Registers the user at `now_s` (if not registered yet) and adds the event (if any) to the
+// user's events, with the time at which the user joined the event.
+// Keeps the `EVENT_ATTENDEES` index in sync with `EARLY_ADOPTERS`.
+fn insert_early_adopter(
+    user_id: Principal,
+    event: Option<(EventName, u32)>,
+    now_s: u32,
+) -> EarlyAdopterData {
+    let is_new_user = EARLY_ADOPTERS.with_borrow_mut(|adopters| {
+        if adopters.contains_key(&user_id) {
+            return false;
+        }
+        adopters.insert(
+            user_id,
+            EarlyAdopterRecord {
+                joined_timestamp_s: now_s,
+            },
+        );
+        true
+    });
+    if is_new_user {
+        record_registration(now_s);
+        mark_documents_stale();
+    }
+    if let Some((event_name, event_joined_timestamp_s)) = event {
+        let key = UserEventKey {
+            principal: user_id,
+            event_id: event_id_or_assign(&event_name),
+        };
+        USER_EVENTS
+            .with_borrow_mut(|user_events| user_events.insert(key, event_joined_timestamp_s));
+        add_event_attendee(
+            event_name,
+            user_id,
+            UserEventRecord {
+                joined_timestamp_s: event_joined_timestamp_s,
+            },
+        );
+    }
+    load_early_adopter(user_id).expect("registered user not found")
+}
+
+// Removes the given event from the caller's events, so that the `EventAttendance`
+// credential for the event can no longer be issued. The caller stays registered
+// with the original `joined_timestamp_s`.
+#[update]
+#[candid_method]
+fn leave_event(event_name: EventName) -> Result<EarlyAdopterResponse, RegisterError> {
+    let user_id = caller();
+    if !EARLY_ADOPTERS.with_borrow(|adopters| adopters.contains_key(&user_id)) {
+        return Err(RegisterError::External(format!(
+            "User {} is not registered",
+            user_id.to_text()
+        )));
+    }
+    let removed = event_id(&event_name).and_then(|event_id| {
+        USER_EVENTS.with_borrow_mut(|user_events| {
+            user_events.remove(&UserEventKey {
+                principal: user_id,
+                event_id,
+            })
+        })
+    });
+    if removed.is_none() {
+        return Err(RegisterError::External(format!(
+            "User {} is not registered for event {}",
+            user_id.to_text(),
+            event_name
+        )));
+    }
+    let data = load_early_adopter(user_id).expect("registered user not found");
+    remove_event_attendee(&event_name, user_id);
+    println!("User {} left event {}.", user_id.to_text(), event_name);
+    Ok(EarlyAdopterResponse::from(data))
+}
+
+// Deletes all the data of the caller. Afterwards, the caller cannot obtain
+// `EarlyAdopter` or `EventAttendance` credentials unless they register again,
+// in which case they get a new `joined_timestamp_s`.
+// Credentials prepared before the deletion can be retrieved until they expire, and still
+// count towards `MAX_PENDING_SIGNATURES_PER_CALLER`. The issuance log entries are kept,
+// but without the principals of the caller.
+#[update]
+#[candid_method]
+fn delete_my_data() -> Result<(), RegisterError> {
+    delete_early_adopter(caller(), false)
+}
+
+/// Overrides the time of registrations and issued credentials (`None` restores the replica time),
+/// for testing. Only built with the `test-clock` feature, the override is reset on upgrade.
+#[cfg(feature = "test-clock")]
+#[update]
+#[candid_method]
+async fn set_time(time_ns: Option<u64>) -> Result<(), RegisterError> {
+    if !is_admin(caller()).await {
+        return Err(RegisterError::External(
+            "Only controllers can set the time".to_string(),
+        ));
+    }
+    TIME_OVERRIDE_NS.with_borrow_mut(|time_override_ns| *time_override_ns = time_ns);
+    Ok(())
+}
+
+#[update]
+#[candid_method]
+async fn delete_user(user_id: Principal) -> Result<(), RegisterError> {
+    if !is_admin(caller()).await {
+        return Err(RegisterError::External(
+            "Only controllers can delete users".to_string(),
+        ));
+    }
+    delete_early_adopter(user_id, true)
+}
+
+#[query]
+#[candid_method(query)]
+async fn list_deletions(start_index: Option<u64>) -> Result<Vec<DeletionData>, RegisterError> {
+    if !is_admin(caller()).await {
+        return Err(RegisterError::External(
+            "Only controllers can list deletions".to_string(),
+        ));
+    }
+    DELETION_LOG.with_borrow(|log| {
+        let start_index = start_index.unwrap_or(0);
+        let end_index = log
+            .len()
+            .min(start_index.saturating_add(MAX_DELETIONS_PER_PAGE));
+        Ok((start_index..end_index)
+            .map(|index| {
+                let record = log.get(index).expect("missing deletion log entry");
+                DeletionData {
+                    index,
+                    timestamp_ns: record.timestamp_ns,
+                    deleted_by_admin: record.deleted_by_admin,
+                    events_count: record.events_count,
+                }
+            })
+            .collect())
+    })
+}
+
+// Removes the user from `EARLY_ADOPTERS`, `USER_EVENTS`, `EVENT_ATTENDEES` and `ISSUANCE_SUBJECTS`,
+// and records the deletion.
+fn delete_early_adopter(user_id: Principal, deleted_by_admin: bool) -> Result<(), RegisterError> {
+    let Some(data) = load_early_adopter(user_id) else {
+        return Err(RegisterError::External(format!(
+            "User {} is not registered",
+            user_id.to_text()
+        )));
+    };
+    EARLY_ADOPTERS.with_borrow_mut(|adopters| adopters.remove(&user_id));
+    forget_registration(data.joined_timestamp_s);
+    mark_documents_stale();
+    remove_user_events(user_id);
+    for event_name in data.events.keys() {
+        remove_event_attendee(event_name, user_id);
+    }
+    remove_issuance_subjects(user_id);
+    DELETION_LOG.with_borrow(|log| {
+        log.append(&DeletionRecord {
+            timestamp_ns: time(),
+            deleted_by_admin,
+            events_count: data.events.len() as u32,
+        })
+        .expect("failed to append to deletion log")
+    });
+    println!("Deleted user data (by admin: {}).", deleted_by_admin);
+    Ok(())
+}
+
+// Exports the events and the early adopters in chunks, starting at the given cursor
+// (or at the beginning, if not set). The issuance and deletion logs are not exported.
+#[query]
+#[candid_method(query)]
+async fn export_state(cursor: Option<ExportCursor>) -> Result<StateChunk, RegisterError> {
+    if !is_admin(caller()).await {
+        return Err(RegisterError::External(
+            "Only controllers can export the state".to_string(),
+        ));
+    }
+    let mut chunk_data = StateChunkData {
+        events: vec![],
+        early_adopters: vec![],
+    };
+    let mut chunk_bytes = 0;
+    let mut next = None;
+    let mut cursor = cursor.unwrap_or(ExportCursor::Events(None));
+    if let ExportCursor::Events(start_after) = &cursor {
+        let start = start_after
+            .clone()
+            .map_or(RangeBound::Unbounded, RangeBound::Excluded);
+        EVENTS.with_borrow(|events| {
+            for (event_name, event) in events.range((start, RangeBound::Unbounded)) {
+                if chunk_bytes >= MAX_STATE_CHUNK_BYTES {
+                    next = chunk_data
+                        .events
+                        .last()
+                        .map(|(last, _)| ExportCursor::Events(Some(last.clone())));
+                    break;
+                }
+                chunk_bytes += event_name.len() + event.to_bytes().len();
+                chunk_data.events.push((event_name, event));
+            }
+        });
+        cursor = ExportCursor::EarlyAdopters(None);
+    }
+    if let (None, ExportCursor::EarlyAdopters(start_after)) = (&next, &cursor) {
+        let start = start_after.map_or(RangeBound::Unbounded, RangeBound::Excluded);
+        EARLY_ADOPTERS.with_borrow(|adopters| {
+            for (principal, record) in adopters.range((start, RangeBound::Unbounded)) {
+                if chunk_bytes >= MAX_STATE_CHUNK_BYTES {
+                    next = chunk_data
+                        .early_adopters
+                        .last()
+                        .map(|(last, _)| ExportCursor::EarlyAdopters(Some(*last)));
+                    break;
+                }
+                let data = early_adopter_data(principal, record);
+                chunk_bytes += principal.as_slice().len() + data.to_bytes().len();
+                chunk_data.early_adopters.push((principal, data));
+            }
+        });
+    }
+    let data = candid::encode_one(&chunk_data).expect("failed to encode StateChunkData");
+    Ok(StateChunk {
+        format_version: STATE_CHUNK_FORMAT_VERSION,
+        checksum: ByteBuf::from(hash_bytes(&data).to_vec()),
+        data: ByteBuf::from(data),
+        next,
+    })
+}
+
+// Imports a chunk returned by `export_state`. Existing events and early adopters
+// with the same keys are overwritten, so importing the same chunk twice is harmless.
+#[update]
+#[candid_method]
+async fn import_state(chunk: StateChunk) -> Result<ImportStateResponse, RegisterError> {
+    if !is_admin(caller()).await {
+        return Err(RegisterError::External(
+            "Only controllers can import the state".to_string(),
+        ));
+    }
+    if chunk.format_version != STATE_CHUNK_FORMAT_VERSION {
+        return Err(RegisterError::External(format!(
+            "Unsupported state chunk format version {}",
+            chunk.format_version
+        )));
+    }
+    if hash_bytes(&chunk.data).as_slice() != chunk.checksum.as_slice() {
+        return Err(RegisterError::External(
+            "State chunk checksum does not match".to_string(),
+        ));
+    }
+    let chunk_data: StateChunkData = candid::decode_one(&chunk.data)
+        .map_err(|e| RegisterError::External(format!("State chunk could not be decoded: {}", e)))?;
+    let response = ImportStateResponse {
+        events_imported: chunk_data.events.len() as u64,
+        early_adopters_imported: chunk_data.early_adopters.len() as u64,
+    };
+    EVENTS.with_borrow_mut(|events| {
+        for (event_name, event) in chunk_data.events {
+            event_id_or_assign(&event_name);
+            events.insert(event_name, event);
+        }
+    });
+    mark_documents_stale();
+    for (principal, data) in chunk_data.early_adopters {
+        replace_early_adopter(principal, data);
+    }
+    println!(
+        "Imported {} events and {} early adopters.",
+        response.events_imported, response.early_adopters_imported
+    );
+    Ok(response)
+}
+
+// Stores the given data for the user, replacing any existing data,
+// and updates the `EVENT_ATTENDEES` index accordingly.
+fn replace_early_adopter(user_id: Principal, data: EarlyAdopterData) {
+    let previous_data = load_early_adopter(user_id);
+    if let Some(previous_data) = &previous_data {
+        forget_registration(previous_data.joined_timestamp_s);
+    }
+    record_registration(data.joined_timestamp_s);
+    store_early_adopter(user_id, &data);
+    mark_documents_stale();
+    for event_name in previous_data
+        .into_iter()
+        .flat_map(|data| data.events.into_keys())
+    {
+        remove_event_attendee(&event_name, user_id);
+    }
+    for (event_name, event) in data.events {
+        add_event_attendee(event_name, user_id, event);
+    }
+}
+
+// Adds the user to the attendees of the event, keeping `EVENT_ATTENDEE_COUNTS` in sync.
+fn add_event_attendee(event_name: EventName, principal: Principal, event: UserEventRecord) {
+    let event_id = event_id_or_assign(&event_name);
+    let previous = EVENT_ATTENDEES.with_borrow_mut(|attendees| {
+        attendees.insert(
+            EventAttendeeKey {
+                event_id,
+                principal,
+            },
+            event,
+        )
+    });
+    if previous.is_none() {
+        EVENT_ATTENDEE_COUNTS.with_borrow_mut(|counts| {
+            let count = counts.get(&event_id).unwrap_or(0);
+            counts.insert(event_id, count + 1);
+        });
+        mark_documents_stale();
+    }
+}
+
+// Removes the user from the attendees of the event, keeping `EVENT_ATTENDEE_COUNTS` in sync.
+fn remove_event_attendee(event_name: &EventName, principal: Principal) {
+    let Some(event_id) = event_id(event_name) else {
+        return;
+    };
+    let removed = EVENT_ATTENDEES.with_borrow_mut(|attendees| {
+        attendees.remove(&EventAttendeeKey {
+            event_id,
+            principal,
+        })
+    });
+    if removed.is_some() {
+        EVENT_ATTENDEE_COUNTS.with_borrow_mut(|counts| match counts.get(&event_id) {
+            Some(count) if count > 1 => {
+                counts.insert(event_id, count - 1);
+            }
+            _ => {
+                counts.remove(&event_id);
+            }
+        });
+        mark_documents_stale();
+    }
+}
+
+fn event_attendee_count(event_name: &EventName) -> u64 {
+    event_id(event_name).map_or(0, |event_id| {
+        EVENT_ATTENDEE_COUNTS.with_borrow(|counts| counts.get(&event_id).unwrap_or(0))
     })
 }
 
-#[update]
-#[candid_method]
-fn register_early_adopter(
-    request: RegisterUserRequest,
-) -> Result<EarlyAdopterResponse, RegisterError> {
-    let user_id = caller();
-    let now_s = (time() / 1_000_000_000) as u32;
-    // Validate event name and code (if present)
-    if let Some(requested_event) = request.event_data.clone() {
-        // Exit early if the event_name is present by is empty.
-        if requested_event.event_name.clone().is_empty() {
-            return Err(RegisterError::External(
-                "event_name cannot be an empty string if present".to_string(),
-            ));
+// Recomputes `EVENT_ATTENDEE_COUNTS` from `EVENT_ATTENDEES`.
+// Migration for data registered before the counts were introduced.
+fn count_event_attendees() {
+    let mut counts_by_id: BTreeMap<EventId, u64> = BTreeMap::new();
+    EVENT_ATTENDEES.with_borrow(|attendees| {
+        for (key, _) in attendees.iter() {
+            *counts_by_id.entry(key.event_id).or_default() += 1;
         }
-        // Exit early if the event doesn't exist.
-        let Some(event_record) = get_event(requested_event.event_name.clone()) else {
-            return Err(RegisterError::Internal(format!(
-                "Event {} does not exist",
-                requested_event.event_name
-            )));
-        };
-        // Exit early if the passed code doesn't match the event code
-        if event_record.registration_code != requested_event.registration_code {
-            return Err(RegisterError::Internal(format!(
-                "Registration code doesn't match for Event {}",
-                requested_event.event_name
-            )));
+    });
+    EVENT_ATTENDEE_COUNTS.with_borrow_mut(|counts| {
+        let event_ids: Vec<EventId> = counts.iter().map(|(event_id, _)| event_id).collect();
+        for event_id in event_ids {
+            counts.remove(&event_id);
         }
+        for (event_id, count) in counts_by_id {
+            counts.insert(event_id, count);
+        }
+    });
+}
+
+// Populates the `EVENT_ATTENDEES` index from `LEGACY_EARLY_ADOPTERS`, if the index is empty.
+// Migration for data registered before the index was introduced.
+fn backfill_event_attendees() {
+    if EVENT_ATTENDEES.with_borrow(|attendees| !attendees.is_empty()) {
+        return;
     }
-    // At this point, the event is present and the code is valid.
-    let current_data = EARLY_ADOPTERS.with_borrow_mut(|adopters| {
-        if let Some(requested_event) = request.event_data.clone() {
-            if let Some(mut data) = adopters.get(&user_id) {
-                let new_event = UserEventRecord {
-                    joined_timestamp_s: now_s,
-                };
-                data.events
-                    .insert(requested_event.event_name.clone(), new_event);
-                data
-            } else {
-                let mut events = BTreeMap::new();
-                let first_event = UserEventRecord {
-                    joined_timestamp_s: now_s,
-                };
-                events.insert(requested_event.event_name.clone(), first_event);
-                let new_data = EarlyAdopterData {
-                    joined_timestamp_s: now_s,
-                    events,
-                };
-                adopters.insert(user_id, new_data.clone());
-                new_data
-            }
+    assign_event_ids();
+    let entries: Vec<_> = LEGACY_EARLY_ADOPTERS.with_borrow(|adopters| adopters.iter().collect());
+    for (principal, data) in entries {
+        for (event_name, event) in data.events {
+            let key = EventAttendeeKey {
+                event_id: event_id_or_assign(&event_name),
+                principal,
+            };
+            EVENT_ATTENDEES.with_borrow_mut(|attendees| attendees.insert(key, event));
+        }
+    }
+}
+
+#[query]
+#[candid_method(query)]
+async fn list_event_attendees(
+    request: ListEventAttendeesRequest,
+) -> Result<ListEventAttendeesResponse, RegisterError> {
+    if !is_admin(caller()).await {
+        return Err(RegisterError::External(
+            "Only controllers can list event attendees".to_string(),
+        ));
+    }
+    if get_event(request.event_name.clone()).is_none() {
+        return Err(RegisterError::External(format!(
+            "Event {} does not exist",
+            request.event_name
+        )));
+    }
+    let max_results = request
+        .max_results
+        .unwrap_or(MAX_ATTENDEES_PER_PAGE)
+        .min(MAX_ATTENDEES_PER_PAGE) as usize;
+    let total_count = event_attendee_count(&request.event_name);
+    // Events without an id have no attendees.
+    let event_id = event_id(&request.event_name);
+    EVENT_ATTENDEES.with_borrow(|attendees| {
+        let mut page = event_id
+            .into_iter()
+            .flat_map(|event_id| event_attendees(attendees, event_id, request.start_after))
+            .take(max_results + 1)
+            .map(|(principal, event)| EventAttendeeData {
+                principal,
+                joined_timestamp_s: event.joined_timestamp_s,
+            })
+            .collect::<Vec<_>>();
+        let next_start_after = if page.len() > max_results {
+            page.truncate(max_results);
+            page.last().map(|attendee| attendee.principal)
         } else {
-            if let Some(data) = adopters.get(&user_id) {
-                data
-            } else {
-                let events = BTreeMap::new();
-                let new_data = EarlyAdopterData {
-                    joined_timestamp_s: now_s,
-                    events,
-                };
-                adopters.insert(user_id, new_data.clone());
-                new_data
+            None
+        };
+        Ok(ListEventAttendeesResponse {
+            attendees: page,
+            total_count,
+            next_start_after,
+        })
+    })
+}
+
+// Iterates over the attendees of the given event, ordered by principal.
+fn event_attendees(
+    attendees: &EventAttendeesMap,
+    event_id: EventId,
+    start_after: Option<Principal>,
+) -> impl Iterator<Item = (Principal, UserEventRecord)> + '_ {
+    let start = match start_after {
+        Some(principal) => RangeBound::Excluded(EventAttendeeKey {
+            event_id,
+            principal,
+        }),
+        None => RangeBound::Included(EventAttendeeKey {
+            event_id,
+            principal: Principal::management_canister(),
+        }),
+    };
+    attendees
+        .range((start, RangeBound::Unbounded))
+        .take_while(move |(key, _)| key.event_id == event_id)
+        .map(|(key, event)| (key.principal, event))
+}
+
+const SECONDS_PER_HOUR: u32 = 60 * 60;
+
+// Counts a registration in `RECENT_REGISTRATIONS`, if it happened in the last day.
+fn record_registration(joined_timestamp_s: u32) {
+    let oldest_hour = now_s().saturating_sub(24 * SECONDS_PER_HOUR) / SECONDS_PER_HOUR;
+    RECENT_REGISTRATIONS.with_borrow_mut(|registrations| {
+        let hour = joined_timestamp_s / SECONDS_PER_HOUR;
+        if hour >= oldest_hour {
+            *registrations.entry(hour).or_default() += 1;
+        }
+        // Drop the hours that are no longer in the window.
+        *registrations = registrations.split_off(&oldest_hour);
+    });
+}
+
+// Reverts `record_registration`, for users that are deleted or replaced.
+fn forget_registration(joined_timestamp_s: u32) {
+    RECENT_REGISTRATIONS.with_borrow_mut(|registrations| {
+        let hour = joined_timestamp_s / SECONDS_PER_HOUR;
+        match registrations.get_mut(&hour) {
+            Some(count) if *count > 1 => *count -= 1,
+            Some(_) => {
+                registrations.remove(&hour);
             }
+            None => {}
         }
     });
-    println!(
-        "Registered User {} at timestamp {}.",
-        user_id.to_text(),
-        current_data.joined_timestamp_s
-    );
-    let events: Vec<UserEventData> = current_data
-        .events
-        .iter()
-        .map(|(event_name, data)| UserEventData {
-            joined_timestamp_s: data.joined_timestamp_s.clone(),
-            event_name: event_name.clone(),
-        })
-        .collect();
-    Ok(EarlyAdopterResponse {
-        joined_timestamp_s: current_data.joined_timestamp_s,
-        events,
+}
+
+fn recent_registrations_count() -> u64 {
+    let oldest_hour = now_s().saturating_sub(24 * SECONDS_PER_HOUR) / SECONDS_PER_HOUR;
+    RECENT_REGISTRATIONS.with_borrow(|registrations| {
+        registrations
+            .range(oldest_hour..)
+            .map(|(_, count)| count)
+            .sum()
     })
 }
 
+fn rebuild_recent_registrations() {
+    RECENT_REGISTRATIONS.with_borrow_mut(|registrations| registrations.clear());
+    let day_ago_s = now_s().saturating_sub(24 * SECONDS_PER_HOUR);
+    EARLY_ADOPTERS.with_borrow(|adopters| {
+        for (_, record) in adopters.iter() {
+            if record.joined_timestamp_s >= day_ago_s {
+                record_registration(record.joined_timestamp_s);
+            }
+        }
+    });
+}
+
+// Counters of the canister calls since the last upgrade.
+#[derive(Default)]
+struct Counters {
+    // Number of prepared credentials per credential type.
+    prepared_credentials: BTreeMap<String, u64>,
+    // Number of failed `prepare_credential` calls per error.
+    prepare_credential_failures: BTreeMap<&'static str, u64>,
+    // Number of rejected id alias credentials per cause, see `IdAliasError`.
+    id_alias_verification_failures: BTreeMap<&'static str, u64>,
+}
+
+fn issue_credential_error_label(err: &IssueCredentialError) -> &'static str {
+    match err {
+        IssueCredentialError::UnknownSubject(_) => "unknown_subject",
+        IssueCredentialError::UnauthorizedSubject(_) => "unauthorized_subject",
+        IssueCredentialError::InvalidIdAlias(_) => "invalid_id_alias",
+        IssueCredentialError::UnsupportedCredentialSpec(_) => "unsupported_credential_spec",
+        IssueCredentialError::SignatureNotFound(_) => "signature_not_found",
+        IssueCredentialError::Internal(_) => "internal",
+    }
+}
+
+// Label values must not contain unescaped quotes, backslashes or line breaks.
+fn escape_label_value(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
 fn metrics() -> Result<Vec<u8>, std::io::Error> {
     let early_adopters_count = EARLY_ADOPTERS.with_borrow(|adopters| adopters.len());
     let mut writer = MetricsEncoder::new(vec![], time() as i64 / 1_000_000);
@@ -742,6 +2856,74 @@ fn metrics() -> Result<Vec<u8>, std::io::Error> {
         early_adopters_count as f64,
         "Number of registered users",
     )?;
+    writer.encode_gauge(
+        "early_adopters_registered_last_24h",
+        recent_registrations_count() as f64,
+        "Number of users registered in the last 24 hours (at hour granularity)",
+    )?;
+    let events_count = EVENTS.with_borrow(|events| events.len());
+    writer.encode_gauge(
+        "early_adopter_events",
+        events_count as f64,
+        "Number of events",
+    )?;
+    let mut attendees_gauge =
+        writer.gauge_vec("event_attendees", "Number of attendees per event")?;
+    for (event_id, count) in
+        EVENT_ATTENDEE_COUNTS.with_borrow(|counts| counts.iter().collect::<Vec<_>>())
+    {
+        let event_name = EVENT_NAMES
+            .with_borrow(|event_names| event_names.get(&event_id))
+            .expect("attendee count without event name");
+        attendees_gauge = attendees_gauge.value(
+            &[("event", escape_label_value(&event_name).as_str())],
+            count as f64,
+        )?;
+    }
+    COUNTERS.with_borrow(|counters| -> Result<(), std::io::Error> {
+        let mut prepared = writer.counter_vec(
+            "credentials_prepared",
+            "Number of prepared credentials per credential type since the last upgrade",
+        )?;
+        for (credential_type, count) in &counters.prepared_credentials {
+            prepared = prepared.value(
+                &[(
+                    "credential_type",
+                    escape_label_value(credential_type).as_str(),
+                )],
+                *count as f64,
+            )?;
+        }
+        let mut failures = writer.counter_vec(
+            "prepare_credential_failures",
+            "Number of failed prepare_credential calls per error since the last upgrade",
+        )?;
+        for (error, count) in &counters.prepare_credential_failures {
+            failures = failures.value(&[("error", *error)], *count as f64)?;
+        }
+        let mut id_alias_failures = writer.counter_vec(
+            "id_alias_verification_failures",
+            "Number of rejected id alias credentials per cause since the last upgrade",
+        )?;
+        for (cause, count) in &counters.id_alias_verification_failures {
+            id_alias_failures = id_alias_failures.value(
+                &[("cause", cause.replace(' ', "_").as_str())],
+                *count as f64,
+            )?;
+        }
+        Ok(())
+    })?;
+    let outstanding_signatures_count = SIGNATURES.with_borrow(|sigs| sigs.len());
+    writer.encode_gauge(
+        "stable_memory_schema_version",
+        SCHEMA_VERSION.with_borrow(|cell| *cell.get()) as f64,
+        "Version of the stable memory layout",
+    )?;
+    writer.encode_gauge(
+        "outstanding_signatures",
+        outstanding_signatures_count as f64,
+        "Number of prepared credential signatures that have not been pruned yet",
+    )?;
     Ok(writer.into_inner())
 }
 
@@ -750,56 +2932,29 @@ fn metrics() -> Result<Vec<u8>, std::io::Error> {
 pub fn http_request(req: HttpRequest) -> HttpResponse {
     let parts: Vec<&str> = req.url.split('?').collect();
     let path = parts[0];
-    match path {
-        "/metrics" => match metrics() {
-            Ok(body) => {
-                let headers = vec![
-                    (
-                        "Content-Type".to_string(),
-                        "text/plain; version=0.0.4".to_string(),
-                    ),
-                    ("Content-Length".to_string(), body.len().to_string()),
-                ];
-                HttpResponse {
-                    status_code: 200,
-                    headers,
-                    body: ByteBuf::from(body),
-                }
-            }
-            Err(err) => HttpResponse {
-                status_code: 500,
-                headers: vec![],
-                body: ByteBuf::from(format!("Failed to encode metrics: {err}")),
-            },
-        },
-        probably_an_asset => {
-            let sigs_root_hash =
-                SIGNATURES.with_borrow(|sigs| pruned(labeled_hash(LABEL_SIG, &sigs.root_hash())));
-            let maybe_asset = ASSETS.with_borrow(|assets| {
-                assets.get_certified_asset(
-                    probably_an_asset,
-                    req.certificate_version,
-                    Some(sigs_root_hash),
-                )
-            });
+    let sigs_root_hash =
+        SIGNATURES.with_borrow(|sigs| pruned(labeled_hash(LABEL_SIG, &sigs.root_hash())));
+    let data_certificate = data_certificate().expect("no data certificate available");
+    let maybe_response = ASSETS.with_borrow(|assets| {
+        assets.certified_response(
+            path,
+            req.certificate_version,
+            &data_certificate,
+            sigs_root_hash,
+        )
+    });
 
-            let mut headers = static_headers();
-            match maybe_asset {
-                Some(asset) => {
-                    headers.extend(asset.headers);
-                    HttpResponse {
-                        status_code: 200,
-                        body: ByteBuf::from(asset.content),
-                        headers,
-                    }
-                }
-                None => HttpResponse {
-                    status_code: 404,
-                    headers,
-                    body: ByteBuf::from(format!("Asset {} not found.", path)),
-                },
-            }
-        }
+    match maybe_response {
+        Some((headers, body)) => HttpResponse {
+            status_code: 200,
+            body: ByteBuf::from(body),
+            headers,
+        },
+        None => HttpResponse {
+            status_code: 404,
+            headers: static_headers(),
+            body: ByteBuf::from(format!("Asset {} not found.", path)),
+        },
     }
 }
 
@@ -814,141 +2969,123 @@ fn verified_credential(subject_principal: Principal, credential_spec: &Credentia
         spec: credential_spec.clone(),
         subject_id: did_for_principal(subject_principal),
         credential_id_url: credential_id_for_principal(subject_principal),
-        issuer_url: ISSUER_URL.to_string(),
-        expiration_timestamp_s: exp_timestamp_s(),
+        issuer_url: issuer_url(),
+        expiration_timestamp_s: exp_timestamp_s(&CanisterClock),
     };
     build_credential_jwt(params)
 }
 
-fn exp_timestamp_s() -> u32 {
-    ((time() + VC_EXPIRATION_PERIOD_NS) / 1_000_000_000) as u32
+// Time of registrations and issued credentials: the replica time, unless overridden by `set_time`
+// (with the `test-clock` feature).
+// Note: the verification of id aliases and the expiration of signatures use the replica time.
+struct CanisterClock;
+
+impl Clock for CanisterClock {
+    #[cfg(feature = "test-clock")]
+    fn now_ns(&self) -> u64 {
+        TIME_OVERRIDE_NS
+            .with_borrow(|time_override_ns| *time_override_ns)
+            .unwrap_or_else(time)
+    }
+
+    #[cfg(not(feature = "test-clock"))]
+    fn now_ns(&self) -> u64 {
+        time()
+    }
 }
 
-// Prepares a unique id for the given subject_principal.
-// The returned URL has the format: "data:text/plain;charset=UTF-8,issuer:...,timestamp_ns:...,subject:..."
-fn credential_id_for_principal(subject_principal: Principal) -> String {
-    let issuer = format!("issuer:{}", ISSUER_URL);
-    let timestamp = format!("timestamp_ns:{}", time());
-    let subject = format!("subject:{}", subject_principal.to_text());
-    format!(
-        "{}{},{},{}",
-        CREDENTIAL_URL_PREFIX, issuer, timestamp, subject
-    )
+fn now_s() -> u32 {
+    (CanisterClock.now_ns() / 1_000_000_000) as u32
 }
 
-#[derive(Debug)]
-pub enum SupportedCredentialType {
-    // Early adopter since <year>
-    EarlyAdopter(i32),
-    // Attendace to specific event
-    EventAttendance(String),
-}
-
-fn verify_credential_spec(
-    spec: &CredentialSpec,
-) -> Result<SupportedCredentialType, IssueCredentialError> {
-    match spec.credential_type.as_str() {
-        "EarlyAdopter" => {
-            let since_year = verify_early_adopter_spec_and_get_since_year(spec)
-                .map_err(IssueCredentialError::UnsupportedCredentialSpec)?;
-            Ok(SupportedCredentialType::EarlyAdopter(since_year))
-        }
-        "EventAttendance" => {
-            let event_name = verify_event_attendance_and_get_event_name(spec)
-                .map_err(IssueCredentialError::UnsupportedCredentialSpec)?;
-            Ok(SupportedCredentialType::EventAttendance(event_name.clone()))
-        }
-        other => Err(IssueCredentialError::UnsupportedCredentialSpec(format!(
-            "Credential {} is not supported",
-            other
-        ))),
+// Prepares a unique id for the given subject_principal.
+// With `CredentialIdScheme::DataUrl`, the returned URL has the format:
+// "data:text/plain;charset=UTF-8,issuer:...,timestamp_ns:...,subject:..."
+// With `CredentialIdScheme::Opaque`, it is a random "urn:uuid:..." URN.
+fn credential_id_for_principal(subject_principal: Principal) -> String {
+    if credential_id_scheme() == CredentialIdScheme::Opaque {
+        return CREDENTIAL_ID_GENERATOR.with_borrow_mut(|generator| {
+            generator
+                .as_mut()
+                .expect("credential id generator is not initialized")
+                .next_id()
+        });
     }
+    data_url_credential_id(&issuer_url(), subject_principal, &CanisterClock)
 }
 
 fn prepare_credential_jwt(
     credential_spec: &CredentialSpec,
     alias_tuple: &AliasTuple,
 ) -> Result<String, IssueCredentialError> {
-    match verify_credential_spec(credential_spec) {
-        Ok(SupportedCredentialType::EarlyAdopter(since_year)) => {
-            let max_timestamp_s = year_to_max_timestamp_s(since_year);
-            EARLY_ADOPTERS.with_borrow(|adopters| {
-                verify_principal_registered_and_authorized(
-                    alias_tuple.id_dapp,
-                    adopters,
-                    max_timestamp_s,
-                )
-            })?;
-            Ok(verified_credential(alias_tuple.id_alias, credential_spec))
-        }
-        Ok(SupportedCredentialType::EventAttendance(event_name)) => {
-            EARLY_ADOPTERS.with_borrow(|adopters| {
-                verify_principal_event_attendance(alias_tuple.id_dapp, adopters, event_name)
-            })?;
-            Ok(verified_credential(alias_tuple.id_alias, credential_spec))
-        }
-        Err(err) => Err(err),
+    let credential_type = verify_credential_spec(credential_spec)?;
+    if let Err(err) =
+        verify_eligibility(&StableRegistrations, alias_tuple.id_dapp, &credential_type)
+    {
+        println!(
+            "*** principal {} is not eligible for credential {:?}: {:?}",
+            alias_tuple.id_dapp.to_text(),
+            credential_type,
+            err
+        );
+        return Err(err);
     }
+    Ok(verified_credential(alias_tuple.id_alias, credential_spec))
 }
 
-fn verify_principal_registered_and_authorized(
-    user: Principal,
-    adopters: &EarlyAdoptersMap,
-    max_timestamp_s: u32,
-) -> Result<(), IssueCredentialError> {
-    let Some(ea_data) = adopters.get(&user) else {
-        println!(
-            "*** principal {} it is not registered for early adopter credential",
-            user.to_text(),
-        );
-        return Err(IssueCredentialError::UnauthorizedSubject(format!(
-            "unregistered principal {}",
-            user.to_text()
-        )));
-    };
-    if ea_data.joined_timestamp_s < max_timestamp_s {
-        Ok(())
-    } else {
-        println!(
-            "*** {} is not authorized for EarlyAdopter credential (joined_timestamp: {}, max_timestamp: {})",
-            user.to_text(), ea_data.joined_timestamp_s, max_timestamp_s
-        );
-        Err(IssueCredentialError::UnauthorizedSubject(format!(
-            "unauthorized principal {}",
-            user.to_text()
-        )))
+// The registrations kept in stable memory.
+struct StableRegistrations;
+
+impl RegistrationStore for StableRegistrations {
+    fn joined_timestamp_s(&self, user: Principal) -> Option<u32> {
+        EARLY_ADOPTERS
+            .with_borrow(|adopters| adopters.get(&user))
+            .map(|record| record.joined_timestamp_s)
+    }
+
+    fn attended_event(&self, user: Principal, event_name: &str) -> bool {
+        event_id(event_name).map_or(false, |event_id| {
+            USER_EVENTS.with_borrow(|user_events| {
+                user_events.contains_key(&UserEventKey {
+                    principal: user,
+                    event_id,
+                })
+            })
+        })
     }
 }
 
-fn verify_principal_event_attendance(
-    user: Principal,
-    adopters: &EarlyAdoptersMap,
-    event_name: String,
-) -> Result<(), IssueCredentialError> {
-    let Some(ea_data) = adopters.get(&user) else {
-        println!(
-            "*** principal {} it is not registered for early adopter credential",
-            user.to_text(),
-        );
-        return Err(IssueCredentialError::UnauthorizedSubject(format!(
-            "unregistered principal {}",
-            user.to_text()
-        )));
-    };
-    match ea_data.events.get(&event_name) {
-        None => {
-            println!(
-                "*** principal {} has not attended the event {}",
-                user.to_text(),
-                event_name
-            );
-            Err(IssueCredentialError::UnauthorizedSubject(format!(
-                "User {} has not attended event {}",
-                user.to_text(),
-                event_name
-            )))
+/// Reasons for turning down a `prepare_credential` call of a caller that requests credentials
+/// too often. `IssueCredentialError` is defined by the VC spec and cannot be extended, so these
+/// are returned as `IssueCredentialError::UnauthorizedSubject`, with the messages below.
+#[derive(Debug)]
+enum ThrottlingError {
+    /// The caller has `max` prepared credentials whose signatures have not expired yet.
+    TooManyPendingCredentials { max: usize },
+    /// The caller exceeded the `prepare_credential_rate_limit`.
+    RateLimitExceeded { retry_after_s: u64 },
+}
+
+impl std::fmt::Display for ThrottlingError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ThrottlingError::TooManyPendingCredentials { max } => write!(
+                f,
+                "too many pending credentials (max. {}), please retry later",
+                max
+            ),
+            ThrottlingError::RateLimitExceeded { retry_after_s } => write!(
+                f,
+                "rate limit exceeded, please retry in {} seconds",
+                retry_after_s
+            ),
         }
-        Some(_) => Ok(()),
+    }
+}
+
+impl From<ThrottlingError> for IssueCredentialError {
+    fn from(err: ThrottlingError) -> Self {
+        IssueCredentialError::UnauthorizedSubject(err.to_string())
     }
 }
 
@@ -968,15 +3105,333 @@ candid::export_service!();
 // Assets
 static ASSET_DIR: Dir<'_> = include_dir!("$CARGO_MANIFEST_DIR/frontend/dist");
 pub fn init_assets() {
+    rebuild_certified_documents();
+    ic_cdk_timers::set_timer_interval(DOCUMENTS_REFRESH_INTERVAL, refresh_certified_documents);
+}
+
+// Certifies the static assets together with the current `API_DOCUMENTS`, `BADGES`
+// (and their logo) and `METRICS_SNAPSHOT`.
+fn certify_assets() {
+    let mut certified_assets = CertifiedHttpAssets::default();
+    for asset in collect_assets(&ASSET_DIR, Some(fixup_html)) {
+        let headers = asset_headers(&asset.content_type.to_mime_type_string(), &asset.encoding);
+        // Directories are served by their `index.html`.
+        if let Some(dir_path) = asset.url_path.strip_suffix("index.html") {
+            certified_assets.certify_asset(dir_path, headers.clone(), asset.content.clone());
+            if dir_path != "/" {
+                certified_assets.certify_asset(
+                    dir_path.trim_end_matches('/'),
+                    headers.clone(),
+                    asset.content.clone(),
+                );
+            }
+        }
+        certified_assets.certify_asset(&asset.url_path, headers, asset.content);
+    }
+    let json_headers = asset_headers(
+        &ContentType::JSON.to_mime_type_string(),
+        &ContentEncoding::Identity,
+    );
+    API_DOCUMENTS.with_borrow(|documents| {
+        for (url_path, content) in documents {
+            certified_assets.certify_asset(url_path, json_headers.clone(), content.clone());
+        }
+    });
+    let svg_headers = asset_headers(
+        &ContentType::SVG.to_mime_type_string(),
+        &ContentEncoding::Identity,
+    );
+    BADGES.with_borrow(|badges| {
+        for (url_path, content) in badges {
+            certified_assets.certify_asset(url_path, svg_headers.clone(), content.clone());
+        }
+    });
+    certified_assets.certify_asset(
+        BADGE_LOGO_PATH,
+        asset_headers(
+            &ContentType::WEBP.to_mime_type_string(),
+            &ContentEncoding::Identity,
+        ),
+        BADGE_LOGO_WEBP.to_vec(),
+    );
+    ASSETS.with_borrow_mut(|assets| *assets = certified_assets);
+    certify_metrics();
+}
+
+// Re-certifies only the `METRICS_SNAPSHOT` served at `/metrics`.
+fn certify_metrics() {
+    let snapshot = METRICS_SNAPSHOT.with_borrow(|snapshot| snapshot.clone());
     ASSETS.with_borrow_mut(|assets| {
-        *assets = CertifiedAssets::certify_assets(
-            collect_assets(&ASSET_DIR, Some(fixup_html)),
-            &static_headers(),
-        );
+        assets.certify_asset(
+            METRICS_PATH,
+            asset_headers(METRICS_CONTENT_TYPE, &ContentEncoding::Identity),
+            snapshot,
+        )
     });
 
     update_root_hash()
 }
+
+// Headers of the response serving an asset, all of which are certified.
+fn asset_headers(content_type: &str, encoding: &ContentEncoding) -> Vec<(String, String)> {
+    let mut headers = static_headers();
+    headers.push(("Content-Type".to_string(), content_type.to_string()));
+    if matches!(encoding, ContentEncoding::GZip) {
+        headers.push(("Content-Encoding".to_string(), "gzip".to_string()));
+    }
+    headers
+}
+
+// Takes a new metrics snapshot and re-certifies it. The JSON documents served under `/api`
+// and the badges are only rebuilt (and the assets re-certified) if their data changed since
+// the last refresh.
+fn refresh_certified_documents() {
+    take_metrics_snapshot();
+    if DOCUMENTS_STALE.with_borrow(|stale| *stale) {
+        build_documents();
+        certify_assets();
+    } else {
+        certify_metrics();
+    }
+}
+
+// Rebuilds all the certified documents and re-certifies the assets, e.g. after a config change.
+fn rebuild_certified_documents() {
+    take_metrics_snapshot();
+    build_documents();
+    certify_assets();
+}
+
+// Marks `API_DOCUMENTS` and `BADGES` for rebuilding on the next `refresh_certified_documents`.
+fn mark_documents_stale() {
+    DOCUMENTS_STALE.with_borrow_mut(|stale| *stale = true);
+}
+
+fn take_metrics_snapshot() {
+    match metrics() {
+        Ok(snapshot) => METRICS_SNAPSHOT.with_borrow_mut(|current| *current = snapshot),
+        Err(err) => println!("Failed to encode metrics: {}", err),
+    }
+}
+
+fn build_documents() {
+    API_DOCUMENTS.with_borrow_mut(|documents| *documents = api_documents());
+    BADGES.with_borrow_mut(|badges| *badges = event_badges());
+    DOCUMENTS_STALE.with_borrow_mut(|stale| *stale = false);
+}
+
+// Public data of an event, served as JSON under `/api/events`.
+#[derive(Serialize)]
+struct ApiEvent {
+    name: EventName,
+    created_timestamp_s: u32,
+    tags: Vec<String>,
+    archived: bool,
+    attendee_count: u64,
+}
+
+// Public statistics of the issuer, served as JSON at `/api/stats`.
+#[derive(Serialize)]
+struct ApiStats {
+    early_adopters: u64,
+    events: u64,
+    event_attendances: u64,
+}
+
+// Builds the JSON documents served under `/api`:
+// `/api/events` lists the events that are not archived,
+// `/api/events/{name}` describes a single event (archived or not),
+// `/api/stats` has the aggregate numbers.
+fn api_documents() -> BTreeMap<String, Vec<u8>> {
+    let events: Vec<ApiEvent> = EVENTS.with_borrow(|events| {
+        events
+            .iter()
+            .map(|(event_name, record)| ApiEvent {
+                attendee_count: event_attendee_count(&event_name),
+                created_timestamp_s: record.created_timestamp_s,
+                tags: record.tags.unwrap_or_default(),
+                archived: record.archived.unwrap_or(false),
+                name: event_name,
+            })
+            .collect()
+    });
+    let stats = ApiStats {
+        early_adopters: EARLY_ADOPTERS.with_borrow(|adopters| adopters.len()),
+        events: events.len() as u64,
+        event_attendances: events.iter().map(|event| event.attendee_count).sum(),
+    };
+    let mut documents = BTreeMap::new();
+    for event in &events {
+        documents.insert(
+            format!("/api/events/{}", encode_path_segment(&event.name)),
+            serde_json::to_vec(event).expect("failed to encode event as JSON"),
+        );
+    }
+    let listed_events: Vec<&ApiEvent> = events.iter().filter(|event| !event.archived).collect();
+    documents.insert(
+        "/api/events".to_string(),
+        serde_json::to_vec(&listed_events).expect("failed to encode events as JSON"),
+    );
+    documents.insert(
+        "/api/stats".to_string(),
+        serde_json::to_vec(&stats).expect("failed to encode stats as JSON"),
+    );
+    documents.extend(issuer_key_documents());
+    documents
+}
+
+// Id of the canister signature key in the issuer DID document.
+const CANISTER_SIG_KEY_FRAGMENT: &str = "canister-sig";
+
+// Builds the documents that tie the issuer URL to the canister signature public key:
+// the `did:web` document of the issuer at `/.well-known/did.json` (and, for an issuer URL
+// with a path, at `{path}/did.json` where `did:web` resolves it), and
+// a JWKS-style key set at `/.well-known/jwks.json`.
+// In both, the key is the DER-encoded `CANISTER_SIG_PK`, as in the `jwk` header of the `vc_jws`.
+fn issuer_key_documents() -> BTreeMap<String, Vec<u8>> {
+    let issuer_did = did_web_for_url(&issuer_url());
+    let key_id = format!("{}#{}", issuer_did, CANISTER_SIG_KEY_FRAGMENT);
+    let jwk = serde_json::json!({
+        "kty": "oct",
+        "alg": "IcCs",
+        "k": URL_SAFE_NO_PAD.encode(CANISTER_SIG_PK.to_der()),
+    });
+    let did_document = serde_json::json!({
+        "@context": ["https://www.w3.org/ns/did/v1"],
+        "id": issuer_did,
+        "alsoKnownAs": [format!("did:icp:{}", ic_cdk::id().to_text())],
+        "verificationMethod": [{
+            "id": key_id,
+            "type": "JsonWebKey2020",
+            "controller": issuer_did,
+            "publicKeyJwk": jwk,
+        }],
+        "assertionMethod": [key_id],
+    });
+    let mut key = jwk;
+    key["kid"] = serde_json::Value::String(key_id);
+    let key_set = serde_json::json!({ "keys": [key] });
+    let did_document =
+        serde_json::to_vec(&did_document).expect("failed to encode DID document as JSON");
+    let mut documents = BTreeMap::from([
+        ("/.well-known/did.json".to_string(), did_document.clone()),
+        (
+            "/.well-known/jwks.json".to_string(),
+            serde_json::to_vec(&key_set).expect("failed to encode key set as JSON"),
+        ),
+    ]);
+    if let Some(path) = url_path(&issuer_url()) {
+        documents.insert(format!("{}/did.json", path), did_document);
+    }
+    documents
+}
+
+// Returns the path of the given https URL without the trailing slash, e.g. `/issuer` for
+// `https://example.com:8443/issuer/`, or `None` if the URL has no path.
+fn url_path(url: &str) -> Option<&str> {
+    let host_and_path = url.trim_start_matches("https://").trim_end_matches('/');
+    host_and_path
+        .find('/')
+        .map(|path_start| &host_and_path[path_start..])
+}
+
+// Returns the `did:web` DID of the given https URL, e.g. `did:web:example.com%3A8443:issuer`
+// for `https://example.com:8443/issuer`.
+fn did_web_for_url(url: &str) -> String {
+    let host_and_path = url.trim_start_matches("https://").trim_end_matches('/');
+    format!(
+        "did:web:{}",
+        host_and_path.replace(':', "%3A").replace('/', ":")
+    )
+}
+
+// Issuer logo shown in the badges, served once at `BADGE_LOGO_PATH`.
+const BADGE_LOGO_WEBP: &[u8] = include_bytes!("../frontend/public/vc-logo.webp");
+const BADGE_LOGO_PATH: &str = "/badge/logo.webp";
+// Longer event names are truncated in the badges.
+const MAX_BADGE_EVENT_NAME_CHARS: usize = 32;
+
+// Builds the SVG badges of all events (archived or not), by URL path.
+fn event_badges() -> BTreeMap<String, Vec<u8>> {
+    EVENTS.with_borrow(|events| {
+        events
+            .iter()
+            .map(|(event_name, record)| {
+                (
+                    format!("/badge/{}.svg", encode_path_segment(&event_name)),
+                    event_badge(&event_name, &record).into_bytes(),
+                )
+            })
+            .collect()
+    })
+}
+
+fn event_badge(event_name: &str, record: &EventRecord) -> String {
+    let mut display_name: String = event_name
+        .chars()
+        .take(MAX_BADGE_EVENT_NAME_CHARS)
+        .collect();
+    if event_name.chars().count() > MAX_BADGE_EVENT_NAME_CHARS {
+        display_name.push('…');
+    }
+    let issuer_url = issuer_url();
+    let issuer_host = issuer_url.trim_start_matches("https://");
+    format!(
+        r##"<svg xmlns="http://www.w3.org/2000/svg" width="400" height="120" viewBox="0 0 400 120" role="img" aria-label="Attended {name}">
+  <rect width="400" height="120" rx="12" fill="#1a1a2e"/>
+  <image href="{logo}" x="16" y="28" width="64" height="64"/>
+  <text x="96" y="42" font-family="sans-serif" font-size="14" fill="#b4b4c8">I attended</text>
+  <text x="96" y="72" font-family="sans-serif" font-size="22" font-weight="bold" fill="#ffffff">{name}</text>
+  <text x="96" y="98" font-family="sans-serif" font-size="13" fill="#b4b4c8">{date} · verified by {issuer}</text>
+</svg>
+"##,
+        name = escape_xml(&display_name),
+        logo = BADGE_LOGO_PATH,
+        date = format_date(record.created_timestamp_s),
+        issuer = escape_xml(issuer_host),
+    )
+}
+
+fn escape_xml(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+// Formats a Unix timestamp (in seconds) as a UTC date, e.g. "2024-02-29".
+// Uses the days-to-civil algorithm from http://howardhinnant.github.io/date_algorithms.html
+fn format_date(timestamp_s: u32) -> String {
+    let days = (timestamp_s / 86_400) as i64 + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days.rem_euclid(146_097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1_460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let shifted_month = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * shifted_month + 2) / 5 + 1;
+    let month = if shifted_month < 10 {
+        shifted_month + 3
+    } else {
+        shifted_month - 9
+    };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+    format!("{:04}-{:02}-{:02}", year, month, day)
+}
+
+// Percent-encodes all characters of the segment except the unreserved ones (RFC 3986).
+fn encode_path_segment(segment: &str) -> String {
+    segment
+        .bytes()
+        .map(|byte| match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                (byte as char).to_string()
+            }
+            _ => format!("%{:02X}", byte),
+        })
+        .collect()
+}
 pub type HeaderField = (String, String);
 
 #[derive(Clone, Debug, CandidType, Deserialize)]
@@ -1007,12 +3462,16 @@ fn fixup_html(html: &str) -> String {
 
 #[cfg(test)]
 mod test {
-    use crate::__export_service;
+    use crate::{EarlyAdopterRecord, EventAttendeeKey, __export_service};
+    use candid::Principal;
     use candid_parser::utils::{service_equal, CandidSource};
+    use ic_stable_structures::Storable;
     use std::path::Path;
 
     /// Checks candid interface type equality by making sure that the service in the did file is
     /// equal to the generated interface.
+    /// The did file describes the production interface, i.e. without the `test-clock` feature.
+    #[cfg(not(feature = "test-clock"))]
     #[test]
     fn check_candid_interface_compatibility() {
         let canister_interface = __export_service();
@@ -1027,4 +3486,25 @@ mod test {
             )
         });
     }
+    #[test]
+    fn should_roundtrip_event_attendee_keys() {
+        let key = EventAttendeeKey {
+            event_id: 7,
+            principal: Principal::from_slice(&[1; 29]),
+        };
+        let bytes = key.to_bytes();
+        assert_eq!(bytes.len(), 34);
+        assert!(EventAttendeeKey::from_bytes(bytes) == key);
+    }
+
+    #[test]
+    fn should_roundtrip_early_adopter_records() {
+        let record = EarlyAdopterRecord {
+            joined_timestamp_s: 1_700_000_000,
+        };
+        let bytes = record.to_bytes();
+        assert_eq!(bytes.len(), 5);
+        let decoded = EarlyAdopterRecord::from_bytes(bytes);
+        assert_eq!(decoded.joined_timestamp_s, record.joined_timestamp_s);
+    }
 }
@@ -0,0 +1,128 @@
+//! Off-chain verification of the credentials issued by the canister, see `verify_credential`.
+
+use crate::credential_spec::verify_credential_spec;
+use candid::Principal;
+use std::fmt;
+use vc_util::issuer_api::{CredentialSpec, IssueCredentialError};
+use vc_util::{
+    did_for_principal, validate_claims_match_spec, verify_credential_jws_with_canister_id,
+};
+
+#[derive(Debug)]
+pub enum VerifyCredentialError {
+    // The credential spec is not supported by the issuer.
+    UnsupportedCredentialSpec(String),
+    // The JWS is malformed, or not signed by the issuer canister.
+    InvalidSignature(String),
+    Expired,
+    // The credential names a different issuer URL.
+    WrongIssuer(Option<String>),
+    // The credential was issued to a different subject.
+    WrongSubject(Option<String>),
+    // The credential does not match the credential spec.
+    SpecMismatch(String),
+}
+
+impl fmt::Display for VerifyCredentialError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::UnsupportedCredentialSpec(msg) => {
+                write!(f, "unsupported credential spec: {}", msg)
+            }
+            Self::InvalidSignature(msg) => write!(f, "invalid signature: {}", msg),
+            Self::Expired => write!(f, "credential expired"),
+            Self::WrongIssuer(Some(issuer)) => write!(f, "unexpected issuer {}", issuer),
+            Self::WrongIssuer(None) => write!(f, "missing issuer"),
+            Self::WrongSubject(Some(subject)) => write!(f, "unexpected subject {}", subject),
+            Self::WrongSubject(None) => write!(f, "missing subject"),
+            Self::SpecMismatch(msg) => write!(f, "credential does not match the spec: {}", msg),
+        }
+    }
+}
+
+impl std::error::Error for VerifyCredentialError {}
+
+/// Verifies a `vc_jws` returned by `get_credential` of the issuer canister `issuer_canister_id`:
+/// the canister signature (wrt. the raw IC root key), the expiry (wrt. `current_time_ns`),
+/// the issuer URL and the credential type and arguments (wrt. `credential_spec`).
+pub fn verify_credential(
+    vc_jws: &str,
+    ic_root_key_raw: &[u8],
+    issuer_canister_id: Principal,
+    issuer_url: &str,
+    credential_spec: &CredentialSpec,
+    current_time_ns: u128,
+) -> Result<(), VerifyCredentialError> {
+    verify_credential_and_get_subject(
+        vc_jws,
+        ic_root_key_raw,
+        issuer_canister_id,
+        issuer_url,
+        credential_spec,
+        current_time_ns,
+    )
+    .map(|_| ())
+}
+
+/// Like `verify_credential`, but additionally checks that the credential was issued
+/// to `subject` (i.e. the id alias of the user).
+pub fn verify_credential_for_subject(
+    vc_jws: &str,
+    ic_root_key_raw: &[u8],
+    issuer_canister_id: Principal,
+    issuer_url: &str,
+    credential_spec: &CredentialSpec,
+    subject: Principal,
+    current_time_ns: u128,
+) -> Result<(), VerifyCredentialError> {
+    let actual_subject = verify_credential_and_get_subject(
+        vc_jws,
+        ic_root_key_raw,
+        issuer_canister_id,
+        issuer_url,
+        credential_spec,
+        current_time_ns,
+    )?;
+    if actual_subject.as_deref() != Some(did_for_principal(subject).as_str()) {
+        return Err(VerifyCredentialError::WrongSubject(actual_subject));
+    }
+    Ok(())
+}
+
+fn verify_credential_and_get_subject(
+    vc_jws: &str,
+    ic_root_key_raw: &[u8],
+    issuer_canister_id: Principal,
+    issuer_url: &str,
+    credential_spec: &CredentialSpec,
+    current_time_ns: u128,
+) -> Result<Option<String>, VerifyCredentialError> {
+    verify_credential_spec(credential_spec).map_err(|err| match err {
+        IssueCredentialError::UnsupportedCredentialSpec(msg) => {
+            VerifyCredentialError::UnsupportedCredentialSpec(msg)
+        }
+        other => VerifyCredentialError::UnsupportedCredentialSpec(format!("{:?}", other)),
+    })?;
+    let claims = verify_credential_jws_with_canister_id(
+        vc_jws,
+        &issuer_canister_id,
+        ic_root_key_raw,
+        current_time_ns,
+    )
+    .map_err(|err| VerifyCredentialError::InvalidSignature(format!("{:?}", err)))?;
+    let current_time_s = (current_time_ns / 1_000_000_000) as i64;
+    if !claims.exp().map_or(false, |exp| exp > current_time_s) {
+        return Err(VerifyCredentialError::Expired);
+    }
+    if claims.iss() != Some(issuer_url) {
+        return Err(VerifyCredentialError::WrongIssuer(
+            claims.iss().map(str::to_string),
+        ));
+    }
+    let vc_claims = claims
+        .vc()
+        .ok_or_else(|| VerifyCredentialError::SpecMismatch("missing VC claims".to_string()))?;
+    validate_claims_match_spec(vc_claims, credential_spec)
+        .map_err(|err| VerifyCredentialError::SpecMismatch(format!("{:?}", err)))?;
+    Ok(claims.sub().map(str::to_string))
+}
@@ -0,0 +1,129 @@
+//! Property-based tests of the parsing of credential specs, independent of the canister.
+
+mod strategies;
+
+use candid::Principal;
+use early_adopter_issuer_lib::consent_message::get_vc_consent_message_en;
+use early_adopter_issuer_lib::credential_spec::{
+    verify_credential_spec, year_to_max_timestamp_s, SupportedCredentialType,
+};
+use early_adopter_issuer_lib::eligibility::{verify_eligibility, RegistrationStore};
+use proptest::prelude::*;
+use strategies::{arb_credential_spec, credential_spec};
+use vc_util::issuer_api::{ArgumentValue, IssueCredentialError};
+
+// A single registered user who attended a single event.
+struct SingleUserStore {
+    joined_timestamp_s: u32,
+    event_name: String,
+}
+
+impl RegistrationStore for SingleUserStore {
+    fn joined_timestamp_s(&self, _user: Principal) -> Option<u32> {
+        Some(self.joined_timestamp_s)
+    }
+
+    fn attended_event(&self, _user: Principal, event_name: &str) -> bool {
+        self.event_name == event_name
+    }
+}
+
+proptest! {
+    #[test]
+    fn should_only_accept_well_formed_specs(spec in arb_credential_spec()) {
+        let arguments = spec.arguments.clone().unwrap_or_default();
+        match verify_credential_spec(&spec) {
+            Ok(SupportedCredentialType::EarlyAdopter(since_year)) => {
+                prop_assert_eq!(spec.credential_type.as_str(), "EarlyAdopter");
+                prop_assert_eq!(arguments.len(), 1);
+                prop_assert!(since_year >= 2024);
+                prop_assert!(matches!(
+                    arguments.get("sinceYear"),
+                    Some(ArgumentValue::Int(year)) if *year == since_year
+                ));
+            }
+            Ok(SupportedCredentialType::EventAttendance(event_name)) => {
+                prop_assert_eq!(spec.credential_type.as_str(), "EventAttendance");
+                prop_assert_eq!(arguments.len(), 1);
+                prop_assert!(matches!(
+                    arguments.get("eventName"),
+                    Some(ArgumentValue::String(name)) if *name == event_name
+                ));
+            }
+            Err(err) => {
+                prop_assert!(matches!(err, IssueCredentialError::UnsupportedCredentialSpec(_)));
+            }
+        }
+    }
+
+    #[test]
+    fn should_render_consent_message_iff_spec_is_supported(spec in arb_credential_spec()) {
+        let consent_message = get_vc_consent_message_en(&spec);
+        match verify_credential_spec(&spec) {
+            Ok(SupportedCredentialType::EarlyAdopter(since_year)) => {
+                let info = consent_message.expect("missing consent message");
+                prop_assert!(info.consent_message.contains(&since_year.to_string()));
+            }
+            Ok(SupportedCredentialType::EventAttendance(event_name)) => {
+                let info = consent_message.expect("missing consent message");
+                prop_assert!(info.consent_message.contains(&event_name));
+            }
+            Err(_) => prop_assert!(consent_message.is_err()),
+        }
+    }
+
+    #[test]
+    fn should_accept_any_early_adopter_year_from_2024(since_year in 2024..=i32::MAX) {
+        let spec = credential_spec("EarlyAdopter", &[("sinceYear", ArgumentValue::Int(since_year))]);
+        prop_assert!(matches!(
+            verify_credential_spec(&spec),
+            Ok(SupportedCredentialType::EarlyAdopter(year)) if year == since_year
+        ));
+        prop_assert!(get_vc_consent_message_en(&spec).is_ok());
+    }
+
+    #[test]
+    fn should_reject_early_adopter_years_before_2024(since_year in i32::MIN..2024) {
+        let spec = credential_spec("EarlyAdopter", &[("sinceYear", ArgumentValue::Int(since_year))]);
+        prop_assert!(verify_credential_spec(&spec).is_err());
+        prop_assert!(get_vc_consent_message_en(&spec).is_err());
+    }
+
+    #[test]
+    fn should_accept_any_event_name(event_name in "\\PC*") {
+        let spec = credential_spec(
+            "EventAttendance",
+            &[("eventName", ArgumentValue::String(event_name.clone()))],
+        );
+        prop_assert!(matches!(
+            verify_credential_spec(&spec),
+            Ok(SupportedCredentialType::EventAttendance(name)) if name == event_name
+        ));
+    }
+
+    #[test]
+    fn should_not_decrease_max_timestamp_with_year(year in 2024..i32::MAX) {
+        prop_assert!(year_to_max_timestamp_s(year) <= year_to_max_timestamp_s(year + 1));
+    }
+
+    #[test]
+    fn should_check_eligibility_consistently_with_spec(
+        spec in arb_credential_spec(),
+        joined_timestamp_s in any::<u32>(),
+        event_name in "\\PC{0,40}",
+    ) {
+        let store = SingleUserStore { joined_timestamp_s, event_name: event_name.clone() };
+        let Ok(credential_type) = verify_credential_spec(&spec) else {
+            return Ok(());
+        };
+        let eligible = verify_eligibility(&store, Principal::anonymous(), &credential_type).is_ok();
+        match credential_type {
+            SupportedCredentialType::EarlyAdopter(since_year) => {
+                prop_assert_eq!(eligible, joined_timestamp_s < year_to_max_timestamp_s(since_year));
+            }
+            SupportedCredentialType::EventAttendance(name) => {
+                prop_assert_eq!(eligible, name == event_name);
+            }
+        }
+    }
+}
@@ -1,6 +1,10 @@
 //! Tests related to issue_credential canister call.
 
+mod strategies;
+
 use assert_matches::assert_matches;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
 use candid::{CandidType, Deserialize, Principal};
 use canister_sig_util::{extract_raw_root_pk_from_der, CanisterSigPublicKey};
 use canister_tests::api::http_request;
@@ -9,22 +13,28 @@ use canister_tests::flows;
 use canister_tests::framework::{
     env, get_wasm_path, principal_1, principal_2, test_principal, time,
 };
+use early_adopter_issuer_lib::verification::{verify_credential, VerifyCredentialError};
+use early_adopter_issuer_lib::DEFAULT_ISSUER_URL;
 use ic_cdk::api::management_canister::provisional::CanisterId;
 use ic_response_verification::types::VerificationInfo;
 use ic_response_verification::verify_request_response_pair;
-use ic_test_state_machine_client::{call_candid, call_candid_as, CanisterSettings};
-use ic_test_state_machine_client::{query_candid_as, CallError, StateMachine};
+use ic_test_state_machine_client::{call_candid_as, CanisterSettings};
+use ic_test_state_machine_client::{query_candid, query_candid_as, CallError, StateMachine};
 use internet_identity_interface::http_gateway::{HttpRequest, HttpResponse};
 use internet_identity_interface::internet_identity::types::vc_mvp::{
     GetIdAliasRequest, PrepareIdAliasRequest,
 };
 use internet_identity_interface::internet_identity::types::FrontendHostname;
 use lazy_static::lazy_static;
+use proptest::prelude::*;
+use proptest::test_runner::TestRunner;
 use serde_bytes::ByteBuf;
+use sha2::{Digest, Sha256};
 use std::collections::HashMap;
 use std::path::PathBuf;
 use std::str;
 use std::time::{Duration, UNIX_EPOCH};
+use strategies::{arb_credential_spec, credential_spec};
 use vc_util::issuer_api::{
     ArgumentValue, CredentialSpec, DerivationOriginData, DerivationOriginError,
     DerivationOriginRequest, GetCredentialRequest, Icrc21ConsentInfo, Icrc21ConsentPreferences,
@@ -47,13 +57,14 @@ const DUMMY_ALIAS_ID_DAPP_PRINCIPAL: &str =
     "nugva-s7c6v-4yszt-koycv-5b623-an7q6-ha2nz-kz6rs-hawgl-nznbe-rqe";
 
 lazy_static! {
-    /// Gzipped Wasm module for the current Early Adopter Issuer build, i.e. the one we're testing
+    /// Gzipped Wasm module for the current Early Adopter Issuer build, i.e. the one we're testing,
+    /// built with the `test-clock` feature.
     pub static ref EARLY_ADOPTER_ISSUER_WASM: Vec<u8> = {
-        let def_path = PathBuf::from("./").join("early_adopter_issuer.wasm.gz");
+        let def_path = PathBuf::from("./").join("early_adopter_issuer_test.wasm.gz");
         let err = format!("
         Could not find Early Adopter Issuer Wasm module for current build.
         I will look for it at {:?} (note that I run from {:?}).
-        You can build the Wasm by running ./build.sh
+        You can build the Wasm (with the `test-clock` feature) by running ./build.sh --test-clock
         ", &def_path,
             &std::env::current_dir().map(|x| x.display().to_string()).unwrap_or_else(|_|
                 "an unknown directory".to_string()));
@@ -61,6 +72,20 @@ lazy_static! {
 
     };
 
+    /// Gzipped Wasm module for the previous Early Adopter Issuer release, used to test upgrades
+    /// from the stable memory layout written by that release.
+    pub static ref EARLY_ADOPTER_ISSUER_PREVIOUS_WASM: Vec<u8> = {
+        let def_path = PathBuf::from("./").join("early_adopter_issuer_previous.wasm.gz");
+        let err = format!("
+        Could not find Early Adopter Issuer Wasm module for the previous release.
+        I will look for it at {:?}, and you can specify another path with the environment variable EARLY_ADOPTER_ISSUER_PREVIOUS_WASM (note that I run from {:?}).
+        You can build it by running ./build.sh on the previous release.
+        ", &def_path,
+            &std::env::current_dir().map(|x| x.display().to_string()).unwrap_or_else(|_|
+                "an unknown directory".to_string()));
+        get_wasm_path("EARLY_ADOPTER_ISSUER_PREVIOUS_WASM".to_string(), &def_path).expect(&err)
+    };
+
     pub static ref II_WASM: Vec<u8> = {
         let def_path = PathBuf::from("./").join("internet_identity.wasm.gz");
         let err = format!("
@@ -98,6 +123,24 @@ pub struct IssuerInit {
     derivation_origin: String,
     /// Frontend hostname to be used by the issuer.
     frontend_hostname: String,
+    /// Rate limit for `prepare_credential` calls per principal.
+    prepare_credential_rate_limit: Option<RateLimitConfig>,
+    /// URL identifying the issuer in the credentials.
+    issuer_url: Option<String>,
+    /// How the ids of the credentials are built.
+    credential_id_scheme: Option<CredentialIdScheme>,
+}
+
+#[derive(CandidType, Deserialize)]
+pub enum CredentialIdScheme {
+    DataUrl,
+    Opaque,
+}
+
+#[derive(CandidType, Deserialize)]
+pub struct RateLimitConfig {
+    capacity: u32,
+    refill_interval_s: u32,
 }
 
 impl Default for IssuerInit {
@@ -107,6 +150,9 @@ impl Default for IssuerInit {
             idp_canister_ids: vec![Principal::from_text(DUMMY_II_CANISTER_ID).unwrap()],
             frontend_hostname: "https://default.host.name".to_string(),
             derivation_origin: "https://default.derivation.origin".to_string(),
+            prepare_credential_rate_limit: None,
+            issuer_url: None,
+            credential_id_scheme: None,
         }
     }
 }
@@ -116,6 +162,8 @@ pub struct EventData {
     pub event_name: String,
     pub registration_code: Option<String>,
     pub created_timestamp_s: u32,
+    pub tags: Vec<String>,
+    pub archived: bool,
 }
 
 #[derive(CandidType, Clone, Deserialize, Debug)]
@@ -135,6 +183,114 @@ pub struct ListEventsResponse {
     pub events: Vec<EventData>,
 }
 
+#[derive(CandidType, Default)]
+pub struct ListEventsPageRequest {
+    pub start_after: Option<String>,
+    pub max_results: Option<u32>,
+    pub created_after_s: Option<u32>,
+    pub created_before_s: Option<u32>,
+    pub tag: Option<String>,
+    pub archived: Option<bool>,
+}
+
+#[derive(CandidType, Deserialize)]
+pub struct ListEventsPageResponse {
+    pub events: Vec<EventData>,
+    pub next_start_after: Option<String>,
+}
+
+#[derive(CandidType)]
+pub struct ListEventAttendeesRequest {
+    pub event_name: String,
+    pub start_after: Option<Principal>,
+    pub max_results: Option<u32>,
+}
+
+#[derive(CandidType, Deserialize, Debug)]
+pub struct EventAttendeeData {
+    pub principal: Principal,
+    pub joined_timestamp_s: u32,
+}
+
+#[derive(CandidType, Deserialize)]
+pub struct ListEventAttendeesResponse {
+    pub attendees: Vec<EventAttendeeData>,
+    pub total_count: u64,
+    pub next_start_after: Option<Principal>,
+}
+
+#[derive(CandidType, Deserialize, Debug)]
+pub struct DeletionData {
+    pub index: u64,
+    pub timestamp_ns: u64,
+    pub deleted_by_admin: bool,
+    pub events_count: u32,
+}
+
+#[derive(CandidType)]
+pub struct ImportedAttendee {
+    pub principal: Principal,
+    pub joined_timestamp_s: Option<u32>,
+}
+
+#[derive(CandidType)]
+pub struct ImportEventAttendeesRequest {
+    pub event_name: String,
+    pub attendees: Vec<ImportedAttendee>,
+}
+
+#[derive(CandidType, Deserialize, Debug, PartialEq)]
+pub enum ImportAttendeeResult {
+    Registered,
+    AlreadyRegistered,
+    Rejected(String),
+}
+
+#[derive(CandidType, Deserialize)]
+pub struct ImportEventAttendeesResponse {
+    pub results: Vec<(Principal, ImportAttendeeResult)>,
+}
+
+#[derive(CandidType, Clone, Deserialize)]
+pub enum ExportCursor {
+    Events(Option<String>),
+    EarlyAdopters(Option<Principal>),
+}
+
+#[derive(CandidType, Clone, Deserialize)]
+pub struct StateChunk {
+    pub format_version: u32,
+    pub data: ByteBuf,
+    pub checksum: ByteBuf,
+    pub next: Option<ExportCursor>,
+}
+
+#[derive(CandidType, Deserialize, Debug)]
+pub struct ImportStateResponse {
+    pub events_imported: u64,
+    pub early_adopters_imported: u64,
+}
+
+#[derive(CandidType, Clone)]
+pub struct VerifyPresentationRequest {
+    pub id_alias_jws: String,
+    pub vc_jws: String,
+    pub credential_spec: CredentialSpec,
+    pub id_dapp: Principal,
+}
+
+#[derive(CandidType, Deserialize, Debug)]
+pub enum VerifyPresentationError {
+    InvalidIdAlias(String),
+    InvalidCredential(String),
+}
+
+#[derive(CandidType)]
+pub struct ArchiveEventRequest {
+    pub event_name: String,
+    pub archived: bool,
+}
+
 #[derive(CandidType, Clone, Deserialize)]
 pub struct RegisterUserEventData {
     pub event_name: String,
@@ -157,6 +313,42 @@ pub struct AddEventResponse {
 pub struct AddEventRequest {
     pub event_name: String,
     pub registration_code: Option<String>,
+    pub tags: Option<Vec<String>>,
+}
+
+#[derive(CandidType, Deserialize)]
+pub struct PreparedContext {
+    pub version: u8,
+    pub credential_spec: CredentialSpec,
+    pub credential_jwt: String,
+}
+
+#[derive(CandidType, Default)]
+pub struct ListIssuancesRequest {
+    pub id_dapp: Option<Principal>,
+    pub credential_type: Option<String>,
+    pub from_timestamp_ns: Option<u64>,
+    pub to_timestamp_ns: Option<u64>,
+    pub start_index: Option<u64>,
+    pub max_results: Option<u32>,
+}
+
+#[derive(CandidType, Deserialize, Debug)]
+pub struct IssuanceData {
+    pub index: u64,
+    pub timestamp_ns: u64,
+    pub id_dapp: Option<Principal>,
+    pub id_alias: Option<Principal>,
+    pub idp_canister_id: Principal,
+    pub credential_type: String,
+    pub arguments: Vec<(String, ArgumentValue)>,
+}
+
+#[derive(CandidType, Deserialize)]
+pub struct ListIssuancesResponse {
+    pub issuances: Vec<IssuanceData>,
+    pub total_count: u64,
+    pub next_index: Option<u64>,
 }
 
 #[derive(CandidType, Debug, Deserialize)]
@@ -194,9 +386,10 @@ mod api {
     pub fn configure(
         env: &StateMachine,
         canister_id: CanisterId,
+        sender: Principal,
         config: &IssuerInit,
-    ) -> Result<(), CallError> {
-        call_candid(env, canister_id, "configure", (config,))
+    ) -> Result<Result<(), EarlyAdopterError>, CallError> {
+        call_candid_as(env, canister_id, sender, "configure", (config,)).map(|(x,)| x)
     }
 
     pub fn vc_consent_message(
@@ -248,6 +441,58 @@ mod api {
         call_candid_as(env, canister_id, sender, "list_events", ()).map(|(x,)| x)
     }
 
+    pub fn list_events_page(
+        env: &StateMachine,
+        canister_id: CanisterId,
+        sender: Principal,
+        request: &ListEventsPageRequest,
+    ) -> Result<Result<ListEventsPageResponse, EarlyAdopterError>, CallError> {
+        query_candid_as(env, canister_id, sender, "list_events_page", (request,)).map(|(x,)| x)
+    }
+
+    pub fn import_event_attendees(
+        env: &StateMachine,
+        canister_id: CanisterId,
+        sender: Principal,
+        request: &ImportEventAttendeesRequest,
+    ) -> Result<Result<ImportEventAttendeesResponse, EarlyAdopterError>, CallError> {
+        call_candid_as(
+            env,
+            canister_id,
+            sender,
+            "import_event_attendees",
+            (request,),
+        )
+        .map(|(x,)| x)
+    }
+
+    pub fn list_event_attendees(
+        env: &StateMachine,
+        canister_id: CanisterId,
+        sender: Principal,
+        request: &ListEventAttendeesRequest,
+    ) -> Result<Result<ListEventAttendeesResponse, EarlyAdopterError>, CallError> {
+        query_candid_as(env, canister_id, sender, "list_event_attendees", (request,)).map(|(x,)| x)
+    }
+
+    pub fn archive_event(
+        env: &StateMachine,
+        canister_id: CanisterId,
+        sender: Principal,
+        request: &ArchiveEventRequest,
+    ) -> Result<Result<EventData, EarlyAdopterError>, CallError> {
+        call_candid_as(env, canister_id, sender, "archive_event", (request,)).map(|(x,)| x)
+    }
+
+    pub fn list_issuances(
+        env: &StateMachine,
+        canister_id: CanisterId,
+        sender: Principal,
+        request: &ListIssuancesRequest,
+    ) -> Result<Result<ListIssuancesResponse, EarlyAdopterError>, CallError> {
+        query_candid_as(env, canister_id, sender, "list_issuances", (request,)).map(|(x,)| x)
+    }
+
     pub fn register_early_adopter(
         env: &StateMachine,
         canister_id: CanisterId,
@@ -264,6 +509,76 @@ mod api {
         .map(|(x,)| x)
     }
 
+    pub fn get_my_registration(
+        env: &StateMachine,
+        canister_id: CanisterId,
+        sender: Principal,
+    ) -> Result<Option<EarlyAdopterResponse>, CallError> {
+        query_candid_as(env, canister_id, sender, "get_my_registration", ()).map(|(x,)| x)
+    }
+
+    pub fn leave_event(
+        env: &StateMachine,
+        canister_id: CanisterId,
+        sender: Principal,
+        event_name: &str,
+    ) -> Result<Result<EarlyAdopterResponse, EarlyAdopterError>, CallError> {
+        call_candid_as(env, canister_id, sender, "leave_event", (event_name,)).map(|(x,)| x)
+    }
+
+    pub fn delete_my_data(
+        env: &StateMachine,
+        canister_id: CanisterId,
+        sender: Principal,
+    ) -> Result<Result<(), EarlyAdopterError>, CallError> {
+        call_candid_as(env, canister_id, sender, "delete_my_data", ()).map(|(x,)| x)
+    }
+
+    pub fn set_time(
+        env: &StateMachine,
+        canister_id: CanisterId,
+        sender: Principal,
+        time_ns: Option<u64>,
+    ) -> Result<Result<(), EarlyAdopterError>, CallError> {
+        call_candid_as(env, canister_id, sender, "set_time", (time_ns,)).map(|(x,)| x)
+    }
+
+    pub fn delete_user(
+        env: &StateMachine,
+        canister_id: CanisterId,
+        sender: Principal,
+        user: Principal,
+    ) -> Result<Result<(), EarlyAdopterError>, CallError> {
+        call_candid_as(env, canister_id, sender, "delete_user", (user,)).map(|(x,)| x)
+    }
+
+    pub fn list_deletions(
+        env: &StateMachine,
+        canister_id: CanisterId,
+        sender: Principal,
+        start_index: Option<u64>,
+    ) -> Result<Result<Vec<DeletionData>, EarlyAdopterError>, CallError> {
+        query_candid_as(env, canister_id, sender, "list_deletions", (start_index,)).map(|(x,)| x)
+    }
+
+    pub fn export_state(
+        env: &StateMachine,
+        canister_id: CanisterId,
+        sender: Principal,
+        cursor: Option<ExportCursor>,
+    ) -> Result<Result<StateChunk, EarlyAdopterError>, CallError> {
+        query_candid_as(env, canister_id, sender, "export_state", (cursor,)).map(|(x,)| x)
+    }
+
+    pub fn import_state(
+        env: &StateMachine,
+        canister_id: CanisterId,
+        sender: Principal,
+        chunk: &StateChunk,
+    ) -> Result<Result<ImportStateResponse, EarlyAdopterError>, CallError> {
+        call_candid_as(env, canister_id, sender, "import_state", (chunk,)).map(|(x,)| x)
+    }
+
     pub fn prepare_credential(
         env: &StateMachine,
         canister_id: CanisterId,
@@ -295,6 +610,14 @@ mod api {
         )
         .map(|(x,)| x)
     }
+
+    pub fn verify_presentation(
+        env: &StateMachine,
+        canister_id: CanisterId,
+        request: &VerifyPresentationRequest,
+    ) -> Result<Result<(), VerifyPresentationError>, CallError> {
+        query_candid(env, canister_id, "verify_presentation", (request,)).map(|(x,)| x)
+    }
 }
 
 #[test]
@@ -472,6 +795,9 @@ fn should_return_derivation_origin_with_custom_init() {
         idp_canister_ids: vec![Principal::from_text(DUMMY_II_CANISTER_ID).unwrap()],
         derivation_origin: "https://custom.derivation_origin".to_string(),
         frontend_hostname: "https://custom.frontend.host.name".to_string(),
+        prepare_credential_rate_limit: None,
+        issuer_url: None,
+        credential_id_scheme: None,
     };
     let canister_id = install_issuer(&env, &custom_init);
     let response = api::derivation_origin(
@@ -505,21 +831,84 @@ fn should_fail_derivation_origin_if_unsupported_origin() {
 }
 
 fn early_adopter_credential_spec() -> CredentialSpec {
-    let mut args = HashMap::new();
-    args.insert("sinceYear".to_string(), ArgumentValue::Int(2024));
-    CredentialSpec {
-        credential_type: "EarlyAdopter".to_string(),
-        arguments: Some(args),
-    }
+    credential_spec("EarlyAdopter", &[("sinceYear", ArgumentValue::Int(2024))])
 }
 
 fn event_attendance_credential_spec(event_name: String) -> CredentialSpec {
-    let mut args = HashMap::new();
-    args.insert("eventName".to_string(), ArgumentValue::String(event_name));
-    CredentialSpec {
-        credential_type: "EventAttendance".to_string(),
-        arguments: Some(args),
-    }
+    credential_spec(
+        "EventAttendance",
+        &[("eventName", ArgumentValue::String(event_name))],
+    )
+}
+
+#[test]
+fn vc_consent_message_and_prepare_credential_agree_on_supported_specs() {
+    let env = env();
+    let issuer_id = install_issuer(
+        &env,
+        &IssuerInit {
+            prepare_credential_rate_limit: Some(RateLimitConfig {
+                capacity: 100,
+                refill_interval_s: 60,
+            }),
+            ..IssuerInit::default()
+        },
+    );
+    let authorized_principal = Principal::from_text(DUMMY_ALIAS_ID_DAPP_PRINCIPAL).unwrap();
+    let request = RegisterUserRequest { event_data: None };
+    api::register_early_adopter(&env, issuer_id, authorized_principal, &request)
+        .expect("API call failed")
+        .expect("failed to register");
+    // The caller registered before 2024 but did not attend any event, so `prepare_credential`
+    // succeeds for every supported EarlyAdopter spec, and fails for every supported
+    // EventAttendance spec because the caller is not authorized.
+    // Note: the number of cases is bounded by the pending signatures allowed per caller.
+    let mut runner = TestRunner::new(ProptestConfig {
+        cases: 10,
+        failure_persistence: None,
+        ..ProptestConfig::default()
+    });
+    runner
+        .run(&arb_credential_spec(), |credential_spec| {
+            let consent_message = api::vc_consent_message(
+                &env,
+                issuer_id,
+                principal_1(),
+                &Icrc21VcConsentMessageRequest {
+                    credential_spec: credential_spec.clone(),
+                    preferences: Icrc21ConsentPreferences {
+                        language: "en-US".to_string(),
+                    },
+                },
+            )
+            .expect("API call failed");
+            let credential_type = credential_spec.credential_type.clone();
+            let prepared_credential = api::prepare_credential(
+                &env,
+                issuer_id,
+                authorized_principal,
+                &PrepareCredentialRequest {
+                    credential_spec,
+                    signed_id_alias: DUMMY_SIGNED_ID_ALIAS.clone(),
+                },
+            )
+            .expect("API call failed");
+            match consent_message {
+                Ok(_) if credential_type == "EarlyAdopter" => {
+                    prop_assert!(prepared_credential.is_ok())
+                }
+                Ok(_) => prop_assert!(matches!(
+                    prepared_credential,
+                    Err(IssueCredentialError::UnauthorizedSubject(_))
+                )),
+                Err(_) => prop_assert!(matches!(
+                    prepared_credential,
+                    Err(IssueCredentialError::UnsupportedCredentialSpec(_))
+                )),
+            }
+            Ok(())
+        })
+        .unwrap();
 }
 
 #[test]
@@ -556,7 +945,50 @@ fn should_fail_prepare_credential_for_wrong_sender() {
     )
     .expect("API call failed");
     assert_matches!(response,
-        Err(IssueCredentialError::InvalidIdAlias(e)) if e.contains("id alias could not be verified")
+        Err(IssueCredentialError::InvalidIdAlias(e)) if e.contains("id alias could not be verified (wrong subject)")
+    );
+}
+
+#[test]
+fn should_fail_prepare_credential_for_expired_id_alias() {
+    let env = env();
+    let issuer_id = install_issuer(&env, &DUMMY_ISSUER_INIT);
+    env.advance_time(Duration::from_secs(3600)); // DUMMY_ALIAS_JWS is valid for 15 minutes
+
+    let response = api::prepare_credential(
+        &env,
+        issuer_id,
+        Principal::from_text(DUMMY_ALIAS_ID_DAPP_PRINCIPAL).unwrap(),
+        &PrepareCredentialRequest {
+            credential_spec: early_adopter_credential_spec(),
+            signed_id_alias: DUMMY_SIGNED_ID_ALIAS.clone(),
+        },
+    )
+    .expect("API call failed");
+    assert_matches!(response,
+        Err(IssueCredentialError::InvalidIdAlias(e)) if e.contains("id alias could not be verified (expired)")
+    );
+}
+
+#[test]
+fn should_fail_prepare_credential_for_malformed_id_alias() {
+    let env = env();
+    let issuer_id = install_issuer(&env, &DUMMY_ISSUER_INIT);
+
+    let response = api::prepare_credential(
+        &env,
+        issuer_id,
+        Principal::from_text(DUMMY_ALIAS_ID_DAPP_PRINCIPAL).unwrap(),
+        &PrepareCredentialRequest {
+            credential_spec: early_adopter_credential_spec(),
+            signed_id_alias: SignedIssuerIdAlias {
+                credential_jws: "not a jws".to_string(),
+            },
+        },
+    )
+    .expect("API call failed");
+    assert_matches!(response,
+        Err(IssueCredentialError::InvalidIdAlias(e)) if e.contains("id alias could not be verified (malformed)")
     );
 }
 
@@ -594,74 +1026,240 @@ fn should_fail_get_credential_for_wrong_sender() {
     )
     .expect("API call failed");
     assert_matches!(get_credential_response,
-        Err(IssueCredentialError::InvalidIdAlias(e)) if e.contains("id alias could not be verified")
+        Err(IssueCredentialError::InvalidIdAlias(e)) if e.contains("id alias could not be verified (wrong subject)")
     );
 }
 
-#[test]
-fn should_fail_prepare_credential_for_anonymous_caller() {
-    let env = env();
-    let issuer_id = install_issuer(&env, &DUMMY_ISSUER_INIT);
-    let response = api::prepare_credential(
-        &env,
+fn prepare_early_adopter_context(env: &StateMachine, issuer_id: CanisterId) -> Vec<u8> {
+    let authorized_principal = Principal::from_text(DUMMY_ALIAS_ID_DAPP_PRINCIPAL).unwrap();
+    let request = RegisterUserRequest { event_data: None };
+    let _ = api::register_early_adopter(env, issuer_id, authorized_principal, &request).unwrap();
+    api::prepare_credential(
+        env,
         issuer_id,
-        Principal::anonymous(),
+        authorized_principal,
         &PrepareCredentialRequest {
             credential_spec: early_adopter_credential_spec(),
             signed_id_alias: DUMMY_SIGNED_ID_ALIAS.clone(),
         },
     )
-    .expect("API call failed");
-    assert_matches!(response,
-        Err(IssueCredentialError::InvalidIdAlias(e)) if e.contains("id alias could not be verified")
-    );
+    .expect("API call failed")
+    .expect("failed to prepare credential")
+    .prepared_context
+    .expect("missing prepared_context")
+    .into_vec()
 }
 
 #[test]
-fn should_fail_prepare_credential_for_wrong_root_key() {
+fn should_fail_get_credential_for_different_credential_spec() {
     let env = env();
-    let issuer_id = install_issuer(
-        &env,
-        &IssuerInit {
-            ic_root_key_der: canister_sig_util::IC_ROOT_PK_DER.to_vec(), // does not match the DUMMY_ROOT_KEY, which is used in DUMMY_ALIAS_JWS
-            ..IssuerInit::default()
-        },
-    );
-    let response = api::prepare_credential(
+    let issuer_id = install_issuer(&env, &DUMMY_ISSUER_INIT);
+    let prepared_context = prepare_early_adopter_context(&env, issuer_id);
+
+    let response = api::get_credential(
         &env,
         issuer_id,
         Principal::from_text(DUMMY_ALIAS_ID_DAPP_PRINCIPAL).unwrap(),
-        &PrepareCredentialRequest {
-            credential_spec: early_adopter_credential_spec(),
+        &GetCredentialRequest {
+            credential_spec: event_attendance_credential_spec("DICE2024".to_string()),
             signed_id_alias: DUMMY_SIGNED_ID_ALIAS.clone(),
+            prepared_context: Some(ByteBuf::from(prepared_context)),
         },
     )
     .expect("API call failed");
-    assert_matches!(response, Err(IssueCredentialError::InvalidIdAlias(_)));
+    assert_matches!(response,
+        Err(IssueCredentialError::UnsupportedCredentialSpec(e)) if e.contains("does not match the prepared credential")
+    );
 }
 
 #[test]
-fn should_fail_prepare_credential_for_wrong_idp_canister_id() {
+fn should_fail_get_credential_for_tampered_prepared_context() {
     let env = env();
-    let issuer_id = install_issuer(
-        &env,
-        &IssuerInit {
-            idp_canister_ids: vec![Principal::from_text("rdmx6-jaaaa-aaaaa-aaadq-cai").unwrap()], // does not match the DUMMY_II_CANISTER_ID, which is used in DUMMY_ALIAS_JWS
-            ..IssuerInit::default()
-        },
-    );
-    let response = api::prepare_credential(
+    let issuer_id = install_issuer(&env, &DUMMY_ISSUER_INIT);
+    let mut prepared_context: PreparedContext =
+        candid::decode_one(&prepare_early_adopter_context(&env, issuer_id))
+            .expect("failed to decode prepared_context");
+    let mut claims: serde_json::Value =
+        serde_json::from_str(&prepared_context.credential_jwt).expect("invalid JWT claims");
+    claims["sub"] = serde_json::Value::String(format!("did:icp:{}", test_principal(2)));
+    prepared_context.credential_jwt = claims.to_string();
+    let prepared_context = candid::encode_one(prepared_context).unwrap();
+
+    let response = api::get_credential(
         &env,
         issuer_id,
         Principal::from_text(DUMMY_ALIAS_ID_DAPP_PRINCIPAL).unwrap(),
-        &PrepareCredentialRequest {
+        &GetCredentialRequest {
             credential_spec: early_adopter_credential_spec(),
             signed_id_alias: DUMMY_SIGNED_ID_ALIAS.clone(),
+            prepared_context: Some(ByteBuf::from(prepared_context)),
         },
     )
     .expect("API call failed");
-    assert_matches!(response, Err(IssueCredentialError::InvalidIdAlias(_)));
-}
+    assert_matches!(response,
+        Err(IssueCredentialError::SignatureNotFound(e)) if e.contains("invalid prepared_context: not prepared")
+    );
+}
+
+#[test]
+fn should_fail_get_credential_for_prepared_context_with_tampered_spec() {
+    let env = env();
+    let issuer_id = install_issuer(&env, &DUMMY_ISSUER_INIT);
+    let mut prepared_context: PreparedContext =
+        candid::decode_one(&prepare_early_adopter_context(&env, issuer_id))
+            .expect("failed to decode prepared_context");
+    prepared_context.credential_spec = event_attendance_credential_spec("DICE2024".to_string());
+    let prepared_context = candid::encode_one(prepared_context).unwrap();
+
+    let response = api::get_credential(
+        &env,
+        issuer_id,
+        Principal::from_text(DUMMY_ALIAS_ID_DAPP_PRINCIPAL).unwrap(),
+        &GetCredentialRequest {
+            credential_spec: event_attendance_credential_spec("DICE2024".to_string()),
+            signed_id_alias: DUMMY_SIGNED_ID_ALIAS.clone(),
+            prepared_context: Some(ByteBuf::from(prepared_context)),
+        },
+    )
+    .expect("API call failed");
+    assert_matches!(response,
+        Err(IssueCredentialError::SignatureNotFound(e)) if e.contains("invalid prepared_context: not prepared")
+    );
+}
+
+#[test]
+fn should_fail_get_credential_for_unstructured_prepared_context() {
+    let env = env();
+    let issuer_id = install_issuer(&env, &DUMMY_ISSUER_INIT);
+
+    let response = api::get_credential(
+        &env,
+        issuer_id,
+        Principal::from_text(DUMMY_ALIAS_ID_DAPP_PRINCIPAL).unwrap(),
+        &GetCredentialRequest {
+            credential_spec: early_adopter_credential_spec(),
+            signed_id_alias: DUMMY_SIGNED_ID_ALIAS.clone(),
+            prepared_context: Some(ByteBuf::from("eyJhbGciOiJub25lIn0.e30.")),
+        },
+    )
+    .expect("API call failed");
+    assert_matches!(response,
+        Err(IssueCredentialError::SignatureNotFound(e)) if e.contains("invalid prepared_context: malformed")
+    );
+}
+
+#[test]
+fn should_fail_prepare_credential_for_anonymous_caller() {
+    let env = env();
+    let issuer_id = install_issuer(&env, &DUMMY_ISSUER_INIT);
+    let response = api::prepare_credential(
+        &env,
+        issuer_id,
+        Principal::anonymous(),
+        &PrepareCredentialRequest {
+            credential_spec: early_adopter_credential_spec(),
+            signed_id_alias: DUMMY_SIGNED_ID_ALIAS.clone(),
+        },
+    )
+    .expect("API call failed");
+    assert_matches!(response,
+        Err(IssueCredentialError::InvalidIdAlias(e)) if e.contains("id alias could not be verified")
+    );
+}
+
+#[test]
+fn should_rate_limit_prepare_credential() {
+    let env = env();
+    let issuer_id = install_issuer(
+        &env,
+        &IssuerInit {
+            prepare_credential_rate_limit: Some(RateLimitConfig {
+                capacity: 2,
+                refill_interval_s: 60,
+            }),
+            ..IssuerInit::default()
+        },
+    );
+    let authorized_principal = Principal::from_text(DUMMY_ALIAS_ID_DAPP_PRINCIPAL).unwrap();
+    let request = RegisterUserRequest { event_data: None };
+    let _ = api::register_early_adopter(&env, issuer_id, authorized_principal, &request).unwrap();
+    let prepare_request = PrepareCredentialRequest {
+        credential_spec: early_adopter_credential_spec(),
+        signed_id_alias: DUMMY_SIGNED_ID_ALIAS.clone(),
+    };
+
+    for _ in 0..2 {
+        api::prepare_credential(&env, issuer_id, authorized_principal, &prepare_request)
+            .expect("API call failed")
+            .expect("failed to prepare credential");
+    }
+    let response = api::prepare_credential(&env, issuer_id, authorized_principal, &prepare_request)
+        .expect("API call failed");
+    assert_matches!(response,
+        Err(IssueCredentialError::UnauthorizedSubject(e)) if e.contains("rate limit exceeded")
+    );
+
+    // The rate limit is per principal.
+    let response = api::prepare_credential(&env, issuer_id, principal_1(), &prepare_request)
+        .expect("API call failed");
+    assert_matches!(response, Err(IssueCredentialError::InvalidIdAlias(_)));
+
+    // A token is added back after the refill interval.
+    env.advance_time(Duration::from_secs(60));
+    api::prepare_credential(&env, issuer_id, authorized_principal, &prepare_request)
+        .expect("API call failed")
+        .expect("failed to prepare credential");
+}
+
+#[test]
+fn should_fail_prepare_credential_for_wrong_root_key() {
+    let env = env();
+    let issuer_id = install_issuer(
+        &env,
+        &IssuerInit {
+            ic_root_key_der: canister_sig_util::IC_ROOT_PK_DER.to_vec(), // does not match the DUMMY_ROOT_KEY, which is used in DUMMY_ALIAS_JWS
+            ..IssuerInit::default()
+        },
+    );
+    let response = api::prepare_credential(
+        &env,
+        issuer_id,
+        Principal::from_text(DUMMY_ALIAS_ID_DAPP_PRINCIPAL).unwrap(),
+        &PrepareCredentialRequest {
+            credential_spec: early_adopter_credential_spec(),
+            signed_id_alias: DUMMY_SIGNED_ID_ALIAS.clone(),
+        },
+    )
+    .expect("API call failed");
+    assert_matches!(response,
+        Err(IssueCredentialError::InvalidIdAlias(e)) if e.contains("id alias could not be verified (invalid signature)")
+    );
+}
+
+#[test]
+fn should_fail_prepare_credential_for_wrong_idp_canister_id() {
+    let env = env();
+    let issuer_id = install_issuer(
+        &env,
+        &IssuerInit {
+            idp_canister_ids: vec![Principal::from_text("rdmx6-jaaaa-aaaaa-aaadq-cai").unwrap()], // does not match the DUMMY_II_CANISTER_ID, which is used in DUMMY_ALIAS_JWS
+            ..IssuerInit::default()
+        },
+    );
+    let response = api::prepare_credential(
+        &env,
+        issuer_id,
+        Principal::from_text(DUMMY_ALIAS_ID_DAPP_PRINCIPAL).unwrap(),
+        &PrepareCredentialRequest {
+            credential_spec: early_adopter_credential_spec(),
+            signed_id_alias: DUMMY_SIGNED_ID_ALIAS.clone(),
+        },
+    )
+    .expect("API call failed");
+    assert_matches!(response,
+        Err(IssueCredentialError::InvalidIdAlias(e)) if e.contains("id alias could not be verified (unknown idp)")
+    );
+}
 
 #[test]
 fn should_prepare_early_adopter_credential_for_authorized_principal() {
@@ -694,6 +1292,7 @@ fn should_fail_to_prepare_event_attendance_credential_for_non_attendees() {
     let event_request = AddEventRequest {
         event_name: attended_event.clone(),
         registration_code: Some(attended_event_code.clone()),
+        tags: None,
     };
     let _ = api::add_event(&env, issuer_id, controller(), &event_request).unwrap();
     let event_data = RegisterUserEventData {
@@ -730,6 +1329,7 @@ fn should_prepare_event_attendance_credential_for_attendees() {
     let event_request = AddEventRequest {
         event_name: event_name.clone(),
         registration_code: Some(event_code.clone()),
+        tags: None,
     };
     let _ = api::add_event(&env, issuer_id, controller(), &event_request).unwrap();
     let event_data = RegisterUserEventData {
@@ -756,6 +1356,162 @@ fn should_prepare_event_attendance_credential_for_attendees() {
     assert_matches!(response, Ok(_));
 }
 
+#[test]
+fn should_limit_pending_signatures_per_caller() {
+    let env = env();
+    let issuer_id = install_issuer(&env, &DUMMY_ISSUER_INIT);
+    let authorized_principal = Principal::from_text(DUMMY_ALIAS_ID_DAPP_PRINCIPAL).unwrap();
+    let request = RegisterUserRequest { event_data: None };
+    let _ = api::register_early_adopter(&env, issuer_id, authorized_principal, &request).unwrap();
+    let prepare_request = PrepareCredentialRequest {
+        credential_spec: early_adopter_credential_spec(),
+        signed_id_alias: DUMMY_SIGNED_ID_ALIAS.clone(),
+    };
+
+    for _ in 0..10 {
+        api::prepare_credential(&env, issuer_id, authorized_principal, &prepare_request)
+            .expect("API call failed")
+            .expect("failed to prepare credential");
+    }
+    let response = api::prepare_credential(&env, issuer_id, authorized_principal, &prepare_request)
+        .expect("API call failed");
+    assert_matches!(response,
+        Err(IssueCredentialError::UnauthorizedSubject(e)) if e.contains("too many pending credentials")
+    );
+}
+
+#[test]
+fn should_log_prepared_credentials() -> Result<(), CallError> {
+    let env = env();
+    let issuer_id = install_issuer(&env, &DUMMY_ISSUER_INIT);
+    let authorized_principal = Principal::from_text(DUMMY_ALIAS_ID_DAPP_PRINCIPAL).unwrap();
+    let _ = prepare_early_adopter_context(&env, issuer_id);
+
+    let response = api::list_issuances(
+        &env,
+        issuer_id,
+        controller(),
+        &ListIssuancesRequest::default(),
+    )?
+    .expect("API call to list issuances failed");
+    assert_eq!(response.total_count, 1);
+    assert_eq!(response.next_index, None);
+    assert_eq!(response.issuances.len(), 1);
+    let issuance = &response.issuances[0];
+    assert_eq!(issuance.id_dapp, Some(authorized_principal));
+    assert_eq!(
+        issuance.idp_canister_id,
+        Principal::from_text(DUMMY_II_CANISTER_ID).unwrap()
+    );
+    assert_eq!(issuance.credential_type, "EarlyAdopter");
+    assert_eq!(
+        issuance.arguments,
+        vec![("sinceYear".to_string(), ArgumentValue::Int(2024))]
+    );
+
+    // Filters are applied.
+    let response = api::list_issuances(
+        &env,
+        issuer_id,
+        controller(),
+        &ListIssuancesRequest {
+            credential_type: Some("EventAttendance".to_string()),
+            ..ListIssuancesRequest::default()
+        },
+    )?
+    .expect("API call to list issuances failed");
+    assert_eq!(response.total_count, 1);
+    assert!(response.issuances.is_empty());
+
+    let response = api::list_issuances(
+        &env,
+        issuer_id,
+        controller(),
+        &ListIssuancesRequest {
+            id_dapp: Some(principal_1()),
+            ..ListIssuancesRequest::default()
+        },
+    )?
+    .expect("API call to list issuances failed");
+    assert!(response.issuances.is_empty());
+
+    Ok(())
+}
+
+#[test]
+fn should_redact_issuances_and_keep_pending_signatures_after_deleting_my_data(
+) -> Result<(), CallError> {
+    let env = env();
+    let issuer_id = install_issuer(&env, &DUMMY_ISSUER_INIT);
+    let authorized_principal = Principal::from_text(DUMMY_ALIAS_ID_DAPP_PRINCIPAL).unwrap();
+    let register_request = RegisterUserRequest { event_data: None };
+    let prepare_request = PrepareCredentialRequest {
+        credential_spec: early_adopter_credential_spec(),
+        signed_id_alias: DUMMY_SIGNED_ID_ALIAS.clone(),
+    };
+    api::register_early_adopter(&env, issuer_id, authorized_principal, &register_request)?
+        .expect("Failed registering user");
+    for _ in 0..10 {
+        api::prepare_credential(&env, issuer_id, authorized_principal, &prepare_request)?
+            .expect("failed to prepare credential");
+    }
+
+    api::delete_my_data(&env, issuer_id, authorized_principal)?.expect("Failed deleting user");
+
+    // The log entries are kept, without the principals of the user.
+    let response = api::list_issuances(
+        &env,
+        issuer_id,
+        controller(),
+        &ListIssuancesRequest::default(),
+    )?
+    .expect("API call to list issuances failed");
+    assert_eq!(response.issuances.len(), 10);
+    for issuance in &response.issuances {
+        assert_eq!(issuance.id_dapp, None);
+        assert_eq!(issuance.id_alias, None);
+        assert_eq!(issuance.credential_type, "EarlyAdopter");
+    }
+    let response = api::list_issuances(
+        &env,
+        issuer_id,
+        controller(),
+        &ListIssuancesRequest {
+            id_dapp: Some(authorized_principal),
+            ..ListIssuancesRequest::default()
+        },
+    )?
+    .expect("API call to list issuances failed");
+    assert!(response.issuances.is_empty());
+
+    // Registering again does not reset the pending credentials of the user.
+    api::register_early_adopter(&env, issuer_id, authorized_principal, &register_request)?
+        .expect("Failed registering user");
+    let response =
+        api::prepare_credential(&env, issuer_id, authorized_principal, &prepare_request)?;
+    assert_matches!(response,
+        Err(IssueCredentialError::UnauthorizedSubject(e)) if e.contains("too many pending credentials")
+    );
+
+    Ok(())
+}
+
+#[test]
+fn only_controllers_can_list_issuances() -> Result<(), CallError> {
+    let env = env();
+    let issuer_id = install_issuer(&env, &DUMMY_ISSUER_INIT);
+
+    let response = api::list_issuances(
+        &env,
+        issuer_id,
+        principal_1(),
+        &ListIssuancesRequest::default(),
+    )?;
+    assert_matches!(response, Err(EarlyAdopterError::External(msg)) if msg.contains("Only controllers"));
+
+    Ok(())
+}
+
 /// Verifies that different credentials are being created including II interactions.
 #[test]
 fn should_issue_credential_e2e() -> Result<(), CallError> {
@@ -815,6 +1571,7 @@ fn should_issue_credential_e2e() -> Result<(), CallError> {
     let event_request = AddEventRequest {
         event_name: event_name.clone(),
         registration_code: Some(event_code.clone()),
+        tags: None,
     };
     let _ = api::add_event(&env, issuer_id, controller(), &event_request).unwrap();
     let event_data = RegisterUserEventData {
@@ -861,68 +1618,374 @@ fn should_issue_credential_e2e() -> Result<(), CallError> {
                 prepared_context: early_adopter_prepared_credential.prepared_context,
             },
         )?;
+        let vc_jws = early_adopter_get_credential_response.unwrap().vc_jws;
+        let current_time_ns = env.time().duration_since(UNIX_EPOCH).unwrap().as_nanos();
         let early_adopter_claims = verify_credential_jws_with_canister_id(
-            &early_adopter_get_credential_response.unwrap().vc_jws,
+            &vc_jws,
             &issuer_id,
             &root_pk_raw,
-            env.time().duration_since(UNIX_EPOCH).unwrap().as_nanos(),
+            current_time_ns,
         )
         .expect("credential verification failed");
         let early_adopter_vc_claims = early_adopter_claims.vc().expect("missing VC claims");
         validate_claims_match_spec(early_adopter_vc_claims, &credential_spec)
             .expect("Claim validation failed");
+
+        // The same checks done off-chain by the verification library.
+        verify_credential(
+            &vc_jws,
+            &root_pk_raw,
+            issuer_id,
+            DEFAULT_ISSUER_URL,
+            &credential_spec,
+            current_time_ns,
+        )
+        .expect("off-chain credential verification failed");
+        assert_matches!(
+            verify_credential(
+                &vc_jws,
+                &root_pk_raw,
+                issuer_id,
+                "https://other-issuer.com",
+                &credential_spec,
+                current_time_ns,
+            ),
+            Err(VerifyCredentialError::WrongIssuer(Some(_)))
+        );
+
+        // The issuer verifies the presentation on behalf of canister-based relying parties.
+        let presentation = VerifyPresentationRequest {
+            id_alias_jws: id_alias_credentials
+                .rp_id_alias_credential
+                .credential_jws
+                .clone(),
+            vc_jws: vc_jws.clone(),
+            credential_spec: credential_spec.clone(),
+            id_dapp: id_alias_credentials.rp_id_alias_credential.id_dapp,
+        };
+        api::verify_presentation(&env, issuer_id, &presentation)?
+            .expect("presentation verification failed");
+        assert_matches!(
+            api::verify_presentation(
+                &env,
+                issuer_id,
+                &VerifyPresentationRequest {
+                    id_dapp: principal_2(),
+                    ..presentation.clone()
+                }
+            )?,
+            Err(VerifyPresentationError::InvalidIdAlias(_))
+        );
+        assert_matches!(
+            api::verify_presentation(
+                &env,
+                issuer_id,
+                &VerifyPresentationRequest {
+                    credential_spec: CredentialSpec {
+                        credential_type: "EarlyAdopter".to_string(),
+                        arguments: Some(HashMap::from([(
+                            "sinceYear".to_string(),
+                            ArgumentValue::Int(2023),
+                        )])),
+                    },
+                    ..presentation
+                }
+            )?,
+            Err(VerifyPresentationError::InvalidCredential(_))
+        );
     }
 
     Ok(())
 }
 
+/// Decodes the claims of the credential issued to the dummy alias.
+fn get_dummy_alias_credential_claims(
+    env: &StateMachine,
+    issuer_id: CanisterId,
+) -> serde_json::Value {
+    let prepared_context = prepare_early_adopter_context(env, issuer_id);
+    let response = api::get_credential(
+        env,
+        issuer_id,
+        Principal::from_text(DUMMY_ALIAS_ID_DAPP_PRINCIPAL).unwrap(),
+        &GetCredentialRequest {
+            credential_spec: early_adopter_credential_spec(),
+            signed_id_alias: DUMMY_SIGNED_ID_ALIAS.clone(),
+            prepared_context: Some(ByteBuf::from(prepared_context)),
+        },
+    )
+    .expect("API call failed")
+    .expect("failed to get credential");
+    let claims = response
+        .vc_jws
+        .split('.')
+        .nth(1)
+        .expect("vc_jws has no claims");
+    serde_json::from_slice(&URL_SAFE_NO_PAD.decode(claims).expect("invalid base64"))
+        .expect("invalid JSON")
+}
+
 #[test]
-fn should_configure() {
+fn should_use_data_url_credential_ids_by_default() {
     let env = env();
     let issuer_id = install_issuer(&env, &DUMMY_ISSUER_INIT);
-    api::configure(&env, issuer_id, &DUMMY_ISSUER_INIT).expect("API call failed");
+    let claims = get_dummy_alias_credential_claims(&env, issuer_id);
+    assert_eq!(claims["iss"], "https://attendance.vc");
+    let credential_id = claims["jti"].as_str().expect("missing jti");
+    assert!(credential_id.starts_with("data:text/plain;charset=UTF-8,issuer:https://attendance.vc"));
 }
 
-/// Verifies that the expected assets is delivered and certified.
 #[test]
-fn issuer_canister_serves_http_assets() -> Result<(), CallError> {
-    fn verify_response_certification(
-        env: &StateMachine,
-        canister_id: CanisterId,
-        request: HttpRequest,
-        http_response: HttpResponse,
-        min_certification_version: u16,
-    ) -> VerificationInfo {
-        verify_request_response_pair(
-            ic_http_certification::HttpRequest {
-                method: request.method,
-                url: request.url,
-                headers: request.headers,
-                body: request.body.into_vec(),
-            },
-            ic_http_certification::HttpResponse {
-                status_code: http_response.status_code,
-                headers: http_response.headers,
-                body: http_response.body.into_vec(),
-                upgrade: None,
-            },
-            canister_id.as_slice(),
-            time(env) as u128,
-            Duration::from_secs(300).as_nanos(),
-            &env.root_key(),
-            min_certification_version as u8,
-        )
-        .unwrap_or_else(|e| panic!("validation failed: {e}"))
-    }
+fn should_use_configured_issuer_url_and_opaque_credential_ids() -> Result<(), CallError> {
+    let env = env();
+    let issuer_id = install_issuer(
+        &env,
+        &IssuerInit {
+            issuer_url: Some("https://issuer.example.com".to_string()),
+            credential_id_scheme: Some(CredentialIdScheme::Opaque),
+            ..IssuerInit::default()
+        },
+    );
+    let claims = get_dummy_alias_credential_claims(&env, issuer_id);
+    assert_eq!(claims["iss"], "https://issuer.example.com");
+    let credential_id = claims["jti"].as_str().expect("missing jti").to_string();
+    assert!(credential_id.starts_with("urn:uuid:"));
+    assert_eq!(credential_id.len(), "urn:uuid:".len() + 36);
+
+    // Credential ids are not repeated.
+    let claims = get_dummy_alias_credential_claims(&env, issuer_id);
+    assert_ne!(claims["jti"].as_str().expect("missing jti"), credential_id);
+
+    let did_document = get_api_document(&env, issuer_id, "/.well-known/did.json")?;
+    assert_eq!(did_document["id"], "did:web:issuer.example.com");
+    Ok(())
+}
 
+#[test]
+fn issuer_canister_serves_did_document_for_issuer_url_with_path() -> Result<(), CallError> {
     let env = env();
-    let canister_id = install_issuer(&env, &DUMMY_ISSUER_INIT);
+    let issuer_id = install_issuer(
+        &env,
+        &IssuerInit {
+            issuer_url: Some("https://example.com:8443/vc/issuer/".to_string()),
+            ..IssuerInit::default()
+        },
+    );
 
-    // for each asset and certification version, fetch the asset, check the HTTP status code, headers and certificate.
+    // did:web resolves `did:web:example.com%3A8443:vc:issuer` to `/vc/issuer/did.json`.
+    for url in ["/vc/issuer/did.json", "/.well-known/did.json"] {
+        let did_document = get_api_document(&env, issuer_id, url)?;
+        assert_eq!(did_document["id"], "did:web:example.com%3A8443:vc:issuer");
+        assert_eq!(
+            did_document["verificationMethod"][0]["id"],
+            "did:web:example.com%3A8443:vc:issuer#canister-sig"
+        );
+    }
+    Ok(())
+}
 
-    for certification_version in 1..=2 {
-        let request = HttpRequest {
-            method: "GET".to_string(),
+#[test]
+fn should_check_since_year_wrt_issuer_time() -> Result<(), CallError> {
+    const EOY_2024_TIMESTAMP_S: u64 = 1735685999;
+    let env = env();
+    let user = Principal::from_text(DUMMY_ALIAS_ID_DAPP_PRINCIPAL).unwrap();
+    for (joined_timestamp_s, since_year, expect_authorized) in [
+        (EOY_2024_TIMESTAMP_S - 1, 2024, true),
+        (EOY_2024_TIMESTAMP_S, 2024, false),
+        (EOY_2024_TIMESTAMP_S, 2025, true),
+    ] {
+        let issuer_id = install_issuer(&env, &DUMMY_ISSUER_INIT);
+        api::set_time(
+            &env,
+            issuer_id,
+            controller(),
+            Some(joined_timestamp_s * 1_000_000_000),
+        )?
+        .expect("failed to set time");
+        let registration = api::register_early_adopter(
+            &env,
+            issuer_id,
+            user,
+            &RegisterUserRequest { event_data: None },
+        )?
+        .expect("failed to register");
+        assert_eq!(registration.joined_timestamp_s as u64, joined_timestamp_s);
+
+        let response = api::prepare_credential(
+            &env,
+            issuer_id,
+            user,
+            &PrepareCredentialRequest {
+                credential_spec: CredentialSpec {
+                    credential_type: "EarlyAdopter".to_string(),
+                    arguments: Some(HashMap::from([(
+                        "sinceYear".to_string(),
+                        ArgumentValue::Int(since_year),
+                    )])),
+                },
+                signed_id_alias: DUMMY_SIGNED_ID_ALIAS.clone(),
+            },
+        )?;
+        if expect_authorized {
+            response.expect("failed to prepare credential");
+        } else {
+            assert_matches!(response, Err(IssueCredentialError::UnauthorizedSubject(_)));
+        }
+    }
+    Ok(())
+}
+
+#[test]
+fn should_authorize_since_years_beyond_the_timestamp_range() -> Result<(), CallError> {
+    const EOY_2024_TIMESTAMP_S: u64 = 1735685999;
+    let env = env();
+    let issuer_id = install_issuer(&env, &DUMMY_ISSUER_INIT);
+    let user = Principal::from_text(DUMMY_ALIAS_ID_DAPP_PRINCIPAL).unwrap();
+    api::set_time(
+        &env,
+        issuer_id,
+        controller(),
+        Some((EOY_2024_TIMESTAMP_S - 1) * 1_000_000_000),
+    )?
+    .expect("failed to set time");
+    api::register_early_adopter(
+        &env,
+        issuer_id,
+        user,
+        &RegisterUserRequest { event_data: None },
+    )?
+    .expect("failed to register");
+
+    // The end of 2160 does not fit into a `u32` timestamp, and would wrap around to 2024.
+    let response = api::prepare_credential(
+        &env,
+        issuer_id,
+        user,
+        &PrepareCredentialRequest {
+            credential_spec: CredentialSpec {
+                credential_type: "EarlyAdopter".to_string(),
+                arguments: Some(HashMap::from([(
+                    "sinceYear".to_string(),
+                    ArgumentValue::Int(2160),
+                )])),
+            },
+            signed_id_alias: DUMMY_SIGNED_ID_ALIAS.clone(),
+        },
+    )?;
+    response.expect("failed to prepare credential");
+    Ok(())
+}
+
+#[test]
+fn should_issue_credentials_expiring_wrt_issuer_time() -> Result<(), CallError> {
+    // Mid 2024, i.e. in time for the EarlyAdopter credential since 2024.
+    const TIME_S: u64 = 1_720_000_000;
+    let env = env();
+    let issuer_id = install_issuer(&env, &DUMMY_ISSUER_INIT);
+    api::set_time(&env, issuer_id, controller(), Some(TIME_S * 1_000_000_000))?
+        .expect("failed to set time");
+
+    let claims = get_dummy_alias_credential_claims(&env, issuer_id);
+    assert_eq!(claims["exp"].as_u64(), Some(TIME_S + 15 * 60));
+    assert!(claims["jti"]
+        .as_str()
+        .expect("missing jti")
+        .contains(&format!("timestamp_ns:{},", TIME_S * 1_000_000_000)));
+
+    // Back to the replica time.
+    api::set_time(&env, issuer_id, controller(), None)?.expect("failed to reset time");
+    let claims = get_dummy_alias_credential_claims(&env, issuer_id);
+    let replica_time_s = env.time().duration_since(UNIX_EPOCH).unwrap().as_secs();
+    assert_eq!(claims["exp"].as_u64(), Some(replica_time_s + 15 * 60));
+    Ok(())
+}
+
+#[test]
+fn should_only_set_time_for_controllers() -> Result<(), CallError> {
+    let env = env();
+    let issuer_id = install_issuer(&env, &DUMMY_ISSUER_INIT);
+    assert_matches!(
+        api::set_time(&env, issuer_id, principal_1(), Some(0))?,
+        Err(EarlyAdopterError::External(_))
+    );
+    Ok(())
+}
+
+#[test]
+fn should_configure() {
+    let env = env();
+    let issuer_id = install_issuer(&env, &DUMMY_ISSUER_INIT);
+    api::configure(&env, issuer_id, controller(), &DUMMY_ISSUER_INIT)
+        .expect("API call failed")
+        .expect("failed to configure the issuer");
+}
+
+#[test]
+fn should_not_configure_if_not_controller() {
+    let env = env();
+    let issuer_id = install_issuer(&env, &DUMMY_ISSUER_INIT);
+    assert_matches!(
+        api::configure(&env, issuer_id, principal_1(), &DUMMY_ISSUER_INIT)
+            .expect("API call failed"),
+        Err(EarlyAdopterError::External(msg)) if msg.contains("Only controllers")
+    );
+}
+
+#[test]
+fn should_not_configure_non_https_issuer_url() {
+    let env = env();
+    let issuer_id = install_issuer(&env, &DUMMY_ISSUER_INIT);
+    let config = IssuerInit {
+        issuer_url: Some("http://issuer.example.com".to_string()),
+        ..IssuerInit::default()
+    };
+    assert_matches!(
+        api::configure(&env, issuer_id, controller(), &config).expect("API call failed"),
+        Err(EarlyAdopterError::External(msg)) if msg.contains("https")
+    );
+}
+
+/// Verifies the certification of the response to the given HTTP request.
+fn verify_response_certification(
+    env: &StateMachine,
+    canister_id: CanisterId,
+    request: HttpRequest,
+    http_response: HttpResponse,
+    min_certification_version: u16,
+) -> VerificationInfo {
+    verify_request_response_pair(
+        ic_http_certification::HttpRequest {
+            method: request.method,
+            url: request.url,
+            headers: request.headers,
+            body: request.body.into_vec(),
+        },
+        ic_http_certification::HttpResponse {
+            status_code: http_response.status_code,
+            headers: http_response.headers,
+            body: http_response.body.into_vec(),
+            upgrade: None,
+        },
+        canister_id.as_slice(),
+        time(env) as u128,
+        Duration::from_secs(300).as_nanos(),
+        &env.root_key(),
+        min_certification_version as u8,
+    )
+    .unwrap_or_else(|e| panic!("validation failed: {e}"))
+}
+
+/// Verifies that the expected assets is delivered and certified.
+#[test]
+fn issuer_canister_serves_http_assets() -> Result<(), CallError> {
+    let env = env();
+    let canister_id = install_issuer(&env, &DUMMY_ISSUER_INIT);
+
+    // for each asset and certification version, fetch the asset, check the HTTP status code, headers and certificate.
+
+    for certification_version in 1..=2 {
+        let request = HttpRequest {
+            method: "GET".to_string(),
             url: "/".to_string(),
             headers: vec![],
             body: ByteBuf::new(),
@@ -944,37 +2007,282 @@ fn issuer_canister_serves_http_assets() -> Result<(), CallError> {
     Ok(())
 }
 
-/// Verifies that the expected assets is delivered and certified.
+/// Fetches the given JSON document of the public API and verifies its certification.
+fn get_api_document(
+    env: &StateMachine,
+    canister_id: CanisterId,
+    url: &str,
+) -> Result<serde_json::Value, CallError> {
+    let request = HttpRequest {
+        method: "GET".to_string(),
+        url: url.to_string(),
+        headers: vec![],
+        body: ByteBuf::new(),
+        certificate_version: Some(2),
+    };
+    let http_response = http_request(env, canister_id, &request)?;
+    assert_eq!(http_response.status_code, 200);
+    let document = serde_json::from_slice(&http_response.body).expect("invalid JSON");
+    let result = verify_response_certification(env, canister_id, request, http_response, 2);
+    assert_eq!(result.verification_version, 2);
+    Ok(document)
+}
+
 #[test]
-fn issuer_canister_serves_metrics_endpoint() -> Result<(), CallError> {
-    fn assert_metrics(
-        env: &StateMachine,
-        canister_id: Principal,
-        expected_substring: &str,
-    ) -> Result<(), CallError> {
+fn issuer_canister_serves_certified_api_documents() -> Result<(), CallError> {
+    let env = env();
+    let issuer_id = install_issuer(&env, &DUMMY_ISSUER_INIT);
+    let stats = get_api_document(&env, issuer_id, "/api/stats")?;
+    assert_eq!(stats["early_adopters"], 0);
+    assert_eq!(stats["events"], 0);
+
+    let mut registration_codes = vec![];
+    for event_name in ["DICE 2024", "ETHDenver"] {
+        let add_event_request = AddEventRequest {
+            event_name: event_name.to_string(),
+            registration_code: None,
+            tags: Some(vec!["conference".to_string()]),
+        };
+        let event = api::add_event(&env, issuer_id, controller(), &add_event_request)?
+            .expect("API call failed");
+        registration_codes.push(event.registration_code.clone());
+        api::register_early_adopter(
+            &env,
+            issuer_id,
+            principal_1(),
+            &RegisterUserRequest {
+                event_data: Some(RegisterUserEventData {
+                    event_name: event.event_name,
+                    registration_code: event.registration_code,
+                }),
+            },
+        )?
+        .expect("failed registering user");
+    }
+    api::register_early_adopter(
+        &env,
+        issuer_id,
+        principal_2(),
+        &RegisterUserRequest { event_data: None },
+    )?
+    .expect("failed registering user");
+    api::archive_event(
+        &env,
+        issuer_id,
+        controller(),
+        &ArchiveEventRequest {
+            event_name: "ETHDenver".to_string(),
+            archived: true,
+        },
+    )?
+    .expect("API call failed");
+
+    // The documents are refreshed periodically.
+    refresh_certified_documents(&env);
+
+    let stats = get_api_document(&env, issuer_id, "/api/stats")?;
+    assert_eq!(stats["early_adopters"], 2);
+    assert_eq!(stats["events"], 2);
+    assert_eq!(stats["event_attendances"], 2);
+
+    let events = get_api_document(&env, issuer_id, "/api/events")?;
+    let events = events.as_array().expect("events is not an array");
+    assert_eq!(events.len(), 1);
+    assert_eq!(events[0]["name"], "DICE 2024");
+    assert_eq!(events[0]["attendee_count"], 1);
+    assert_eq!(events[0]["tags"][0], "conference");
+
+    let event = get_api_document(&env, issuer_id, "/api/events/ETHDenver")?;
+    assert_eq!(event["archived"], true);
+    assert_eq!(event["attendee_count"], 1);
+    let event = get_api_document(&env, issuer_id, "/api/events/DICE%202024")?;
+    assert_eq!(event["name"], "DICE 2024");
+
+    // Registration codes are never exposed.
+    for url in [
+        "/api/events",
+        "/api/events/ETHDenver",
+        "/api/events/DICE%202024",
+    ] {
+        let document = get_api_document(&env, issuer_id, url)?.to_string();
+        for registration_code in &registration_codes {
+            assert!(!document.contains(registration_code.as_str()));
+        }
+    }
+    Ok(())
+}
+
+/// Interval at which the issuer refreshes the certified metrics snapshot.
+const DOCUMENTS_REFRESH_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Lets the issuer take a new metrics snapshot, and rebuild the documents whose data changed.
+fn refresh_certified_documents(env: &StateMachine) {
+    env.advance_time(DOCUMENTS_REFRESH_INTERVAL + Duration::from_secs(1));
+    env.tick();
+}
+
+#[test]
+fn issuer_canister_serves_did_document_and_keys() -> Result<(), CallError> {
+    let env = env();
+    let issuer_id = install_issuer(&env, &DUMMY_ISSUER_INIT);
+    let canister_sig_pk =
+        CanisterSigPublicKey::new(issuer_id, Sha256::digest("EarlyAdopter").to_vec());
+    let expected_key = URL_SAFE_NO_PAD.encode(canister_sig_pk.to_der());
+
+    let did_document = get_api_document(&env, issuer_id, "/.well-known/did.json")?;
+    assert_eq!(did_document["id"], "did:web:attendance.vc");
+    assert_eq!(
+        did_document["alsoKnownAs"][0],
+        format!("did:icp:{}", issuer_id.to_text())
+    );
+    let method = &did_document["verificationMethod"][0];
+    assert_eq!(method["id"], "did:web:attendance.vc#canister-sig");
+    assert_eq!(method["publicKeyJwk"]["alg"], "IcCs");
+    assert_eq!(method["publicKeyJwk"]["k"], expected_key.as_str());
+    assert_eq!(did_document["assertionMethod"][0], method["id"]);
+
+    let key_set = get_api_document(&env, issuer_id, "/.well-known/jwks.json")?;
+    assert_eq!(key_set["keys"][0]["kid"], method["id"]);
+    assert_eq!(key_set["keys"][0]["k"], expected_key.as_str());
+    Ok(())
+}
+
+#[test]
+fn issuer_canister_serves_certified_event_badges() -> Result<(), CallError> {
+    let env = env();
+    let issuer_id = install_issuer(&env, &DUMMY_ISSUER_INIT);
+    for event_name in ["DICE2024", "R&D <2024>"] {
+        let add_event_request = AddEventRequest {
+            event_name: event_name.to_string(),
+            registration_code: None,
+            tags: None,
+        };
+        api::add_event(&env, issuer_id, controller(), &add_event_request)?
+            .expect("API call failed");
+    }
+    refresh_certified_documents(&env);
+
+    for (url, expected_name) in [
+        ("/badge/DICE2024.svg", "DICE2024"),
+        ("/badge/R%26D%20%3C2024%3E.svg", "R&amp;D &lt;2024&gt;"),
+    ] {
         let request = HttpRequest {
             method: "GET".to_string(),
-            url: "/metrics".to_string(),
+            url: url.to_string(),
             headers: vec![],
             body: ByteBuf::new(),
-            certificate_version: Some(1),
+            certificate_version: Some(2),
         };
-        let http_response = http_request(&env, canister_id, &request)?;
+        let http_response = http_request(&env, issuer_id, &request)?;
         assert_eq!(http_response.status_code, 200);
+        let badge = String::from_utf8(http_response.body.to_vec()).expect("invalid UTF-8");
+        assert!(badge.starts_with("<svg"));
+        assert!(badge.contains(expected_name));
+        assert!(badge.contains(r#"href="/badge/logo.webp""#));
+        let result = verify_response_certification(&env, issuer_id, request, http_response, 2);
+        assert_eq!(result.verification_version, 2);
+    }
 
-        match str::from_utf8(&http_response.body) {
-            Ok(metrics_str) => {
-                print!("{}", metrics_str.to_string());
-                assert!(metrics_str.contains(expected_substring));
-            }
-            Err(_) => {
-                assert!(false);
-            }
+    // The logo is served once for all badges.
+    let request = HttpRequest {
+        method: "GET".to_string(),
+        url: "/badge/logo.webp".to_string(),
+        headers: vec![],
+        body: ByteBuf::new(),
+        certificate_version: Some(2),
+    };
+    let http_response = http_request(&env, issuer_id, &request)?;
+    assert_eq!(http_response.status_code, 200);
+    let result = verify_response_certification(&env, issuer_id, request, http_response, 2);
+    assert_eq!(result.verification_version, 2);
+    Ok(())
+}
+
+/// Checks the current metrics snapshot, see `refresh_certified_documents` for taking a new one.
+fn assert_metrics(
+    env: &StateMachine,
+    canister_id: Principal,
+    expected_substring: &str,
+) -> Result<(), CallError> {
+    let request = HttpRequest {
+        method: "GET".to_string(),
+        url: "/metrics".to_string(),
+        headers: vec![],
+        body: ByteBuf::new(),
+        certificate_version: Some(2),
+    };
+    let http_response = http_request(&env, canister_id, &request)?;
+    assert_eq!(http_response.status_code, 200);
+    let body = http_response.body.clone();
+    verify_response_certification(env, canister_id, request, http_response, 2);
+
+    match str::from_utf8(&body) {
+        Ok(metrics_str) => {
+            print!("{}", metrics_str.to_string());
+            assert!(metrics_str.contains(expected_substring));
+        }
+        Err(_) => {
+            assert!(false);
+        }
+    };
+
+    Ok(())
+}
+
+#[test]
+fn issuer_canister_serves_certified_metrics() -> Result<(), CallError> {
+    let env = env();
+    let canister_id = install_issuer(&env, &DUMMY_ISSUER_INIT);
+    api::register_early_adopter(
+        &env,
+        canister_id,
+        principal_1(),
+        &RegisterUserRequest { event_data: None },
+    )?
+    .expect("Failed registering user");
+
+    // The snapshot is only refreshed periodically.
+    assert_metrics(&env, canister_id, "early_adopters 0")?;
+    env.advance_time(DOCUMENTS_REFRESH_INTERVAL + Duration::from_secs(1));
+    env.tick();
+    assert_metrics(&env, canister_id, "early_adopters 1")?;
+
+    // The metrics are served in the Prometheus text format, with the content type certified
+    // under certification v2.
+    for certification_version in 1..=2 {
+        let request = HttpRequest {
+            method: "GET".to_string(),
+            url: "/metrics".to_string(),
+            headers: vec![],
+            body: ByteBuf::new(),
+            certificate_version: Some(certification_version),
         };
+        let http_response = http_request(&env, canister_id, &request)?;
+        assert_eq!(http_response.status_code, 200);
+        let content_types: Vec<&str> = http_response
+            .headers
+            .iter()
+            .filter(|(name, _)| name.eq_ignore_ascii_case("Content-Type"))
+            .map(|(_, value)| value.as_str())
+            .collect();
+        assert_eq!(content_types, vec!["text/plain; version=0.0.4"]);
 
-        Ok(())
+        let result = verify_response_certification(
+            &env,
+            canister_id,
+            request,
+            http_response,
+            certification_version,
+        );
+        assert_eq!(result.verification_version, certification_version);
     }
 
+    Ok(())
+}
+
+/// Verifies that the expected assets is delivered and certified.
+#[test]
+fn issuer_canister_serves_metrics_endpoint() -> Result<(), CallError> {
     let env = env();
     let issuer_id = install_issuer(&env, &DUMMY_ISSUER_INIT);
     let request = RegisterUserRequest { event_data: None };
@@ -984,6 +2292,7 @@ fn issuer_canister_serves_metrics_endpoint() -> Result<(), CallError> {
     api::register_early_adopter(&env, issuer_id, principal_1(), &request)?
         .expect("Failed registering user");
 
+    refresh_certified_documents(&env);
     assert_metrics(&env, issuer_id, "early_adopters 1")?;
 
     env.advance_time(std::time::Duration::from_secs(2));
@@ -992,8 +2301,111 @@ fn issuer_canister_serves_metrics_endpoint() -> Result<(), CallError> {
         .expect("Failed registering user");
 
     env.advance_time(std::time::Duration::from_secs(2));
+    refresh_certified_documents(&env);
 
     assert_metrics(&env, issuer_id, "early_adopters 2")?;
+    assert_metrics(&env, issuer_id, "outstanding_signatures 0")?;
+
+    let authorized_principal = Principal::from_text(DUMMY_ALIAS_ID_DAPP_PRINCIPAL).unwrap();
+    api::register_early_adopter(&env, issuer_id, authorized_principal, &request)?
+        .expect("Failed registering user");
+    api::prepare_credential(
+        &env,
+        issuer_id,
+        authorized_principal,
+        &PrepareCredentialRequest {
+            credential_spec: early_adopter_credential_spec(),
+            signed_id_alias: DUMMY_SIGNED_ID_ALIAS.clone(),
+        },
+    )?
+    .expect("failed to prepare credential");
+
+    refresh_certified_documents(&env);
+    assert_metrics(&env, issuer_id, "outstanding_signatures 1")?;
+
+    Ok(())
+}
+
+#[test]
+fn issuer_canister_serves_event_and_credential_metrics() -> Result<(), CallError> {
+    let env = env();
+    let issuer_id = install_issuer(&env, &DUMMY_ISSUER_INIT);
+    let event_name = "DICE2024".to_string();
+    let event_code = "code".to_string();
+    let event_request = AddEventRequest {
+        event_name: event_name.clone(),
+        registration_code: Some(event_code.clone()),
+        tags: None,
+    };
+    api::add_event(&env, issuer_id, controller(), &event_request)?.expect("API call failed");
+    let request = RegisterUserRequest {
+        event_data: Some(RegisterUserEventData {
+            event_name,
+            registration_code: event_code,
+        }),
+    };
+    api::register_early_adopter(&env, issuer_id, principal_1(), &request)?
+        .expect("Failed registering user");
+
+    refresh_certified_documents(&env);
+    assert_metrics(&env, issuer_id, "early_adopter_events 1")?;
+    assert_metrics(&env, issuer_id, "early_adopters_registered_last_24h 1")?;
+    assert_metrics(&env, issuer_id, "event_attendees{event=\"DICE2024\"} 1")?;
+
+    let _ = prepare_early_adopter_context(&env, issuer_id);
+    refresh_certified_documents(&env);
+    assert_metrics(
+        &env,
+        issuer_id,
+        "credentials_prepared{credential_type=\"EarlyAdopter\"} 1",
+    )?;
+
+    // principal_1 is not the subject of the id alias
+    let response = api::prepare_credential(
+        &env,
+        issuer_id,
+        principal_1(),
+        &PrepareCredentialRequest {
+            credential_spec: early_adopter_credential_spec(),
+            signed_id_alias: DUMMY_SIGNED_ID_ALIAS.clone(),
+        },
+    )?;
+    assert_matches!(response, Err(IssueCredentialError::InvalidIdAlias(_)));
+    refresh_certified_documents(&env);
+    assert_metrics(
+        &env,
+        issuer_id,
+        "prepare_credential_failures{error=\"invalid_id_alias\"} 1",
+    )?;
+    assert_metrics(
+        &env,
+        issuer_id,
+        "id_alias_verification_failures{cause=\"wrong_subject\"} 1",
+    )?;
+
+    Ok(())
+}
+
+#[test]
+fn should_only_count_registrations_of_the_last_day() -> Result<(), CallError> {
+    let env = env();
+    let issuer_id = install_issuer(&env, &DUMMY_ISSUER_INIT);
+    let request = RegisterUserRequest { event_data: None };
+    for user in [principal_1(), principal_2()] {
+        api::register_early_adopter(&env, issuer_id, user, &request)?
+            .expect("Failed registering user");
+    }
+    refresh_certified_documents(&env);
+    assert_metrics(&env, issuer_id, "early_adopters_registered_last_24h 2")?;
+
+    api::delete_my_data(&env, issuer_id, principal_2())?.expect("Failed deleting user");
+    refresh_certified_documents(&env);
+    assert_metrics(&env, issuer_id, "early_adopters_registered_last_24h 1")?;
+
+    env.advance_time(Duration::from_secs(25 * 60 * 60));
+    refresh_certified_documents(&env);
+    assert_metrics(&env, issuer_id, "early_adopters_registered_last_24h 0")?;
+    assert_metrics(&env, issuer_id, "early_adopters 1")?;
 
     Ok(())
 }
@@ -1025,22 +2437,207 @@ fn should_not_overwrite_the_first_registration() -> Result<(), CallError> {
         status_2_user_a.joined_timestamp_s
     );
 
-    // Re-register user a again, it's timestamp should still not change
-    env.advance_time(std::time::Duration::from_secs(2));
-    let status_3_user_a = api::register_early_adopter(&env, issuer_id, user_a, &request)?
-        .expect("Failed getting status for user a");
-    assert_eq!(
-        status_1_user_a.joined_timestamp_s,
-        status_3_user_a.joined_timestamp_s
-    );
+    // Re-register user a again, it's timestamp should still not change
+    env.advance_time(std::time::Duration::from_secs(2));
+    let status_3_user_a = api::register_early_adopter(&env, issuer_id, user_a, &request)?
+        .expect("Failed getting status for user a");
+    assert_eq!(
+        status_1_user_a.joined_timestamp_s,
+        status_3_user_a.joined_timestamp_s
+    );
+
+    // Re-register user b, it's timestamp should not change
+    let status_2_user_b = api::register_early_adopter(&env, issuer_id, user_b, &request)?
+        .expect("Failed getting status for user b");
+    assert_eq!(
+        status_1_user_b.joined_timestamp_s,
+        status_2_user_b.joined_timestamp_s
+    );
+
+    Ok(())
+}
+
+#[test]
+fn should_get_my_registration_without_registering() -> Result<(), CallError> {
+    let env = env();
+    let issuer_id = install_issuer(&env, &DUMMY_ISSUER_INIT);
+    let user = principal_1();
+
+    let registration = api::get_my_registration(&env, issuer_id, user)?;
+    assert!(registration.is_none());
+    // The query does not register the user.
+    let registration = api::get_my_registration(&env, issuer_id, user)?;
+    assert!(registration.is_none());
+
+    let request = RegisterUserRequest { event_data: None };
+    let status = api::register_early_adopter(&env, issuer_id, user, &request)?
+        .expect("Failed registering user");
+    let registration =
+        api::get_my_registration(&env, issuer_id, user)?.expect("user is not registered");
+    assert_eq!(registration.joined_timestamp_s, status.joined_timestamp_s);
+    assert!(registration.events.is_empty());
+
+    // Other users are still not registered.
+    assert!(api::get_my_registration(&env, issuer_id, principal_2())?.is_none());
+
+    Ok(())
+}
+
+#[test]
+fn should_leave_event() -> Result<(), CallError> {
+    let env = env();
+    let issuer_id = install_issuer(&env, &DUMMY_ISSUER_INIT);
+    let authorized_principal = Principal::from_text(DUMMY_ALIAS_ID_DAPP_PRINCIPAL).unwrap();
+    for event_name in ["event A", "event B"] {
+        let event_request = AddEventRequest {
+            event_name: event_name.to_string(),
+            registration_code: Some("code".to_string()),
+            tags: None,
+        };
+        api::add_event(&env, issuer_id, controller(), &event_request)?.expect("API call failed");
+    }
+    let status_before = api::register_early_adopter(
+        &env,
+        issuer_id,
+        authorized_principal,
+        &RegisterUserRequest { event_data: None },
+    )?
+    .expect("Failed registering user");
+    env.advance_time(Duration::from_secs(2));
+    for event_name in ["event A", "event B"] {
+        let request = RegisterUserRequest {
+            event_data: Some(RegisterUserEventData {
+                event_name: event_name.to_string(),
+                registration_code: "code".to_string(),
+            }),
+        };
+        api::register_early_adopter(&env, issuer_id, authorized_principal, &request)?
+            .expect("Failed registering user");
+    }
+
+    let status_after = api::leave_event(&env, issuer_id, authorized_principal, "event A")?
+        .expect("Failed leaving event");
+    assert_eq!(
+        status_before.joined_timestamp_s,
+        status_after.joined_timestamp_s
+    );
+    assert_eq!(status_after.events.len(), 1);
+    assert_eq!(status_after.events[0].event_name, "event B");
+
+    let response = api::prepare_credential(
+        &env,
+        issuer_id,
+        authorized_principal,
+        &PrepareCredentialRequest {
+            credential_spec: event_attendance_credential_spec("event A".to_string()),
+            signed_id_alias: DUMMY_SIGNED_ID_ALIAS.clone(),
+        },
+    )?;
+    assert_matches!(response, Err(IssueCredentialError::UnauthorizedSubject(_)));
+    let response = api::prepare_credential(
+        &env,
+        issuer_id,
+        authorized_principal,
+        &PrepareCredentialRequest {
+            credential_spec: event_attendance_credential_spec("event B".to_string()),
+            signed_id_alias: DUMMY_SIGNED_ID_ALIAS.clone(),
+        },
+    )?;
+    assert_matches!(response, Ok(_));
+
+    // Leaving the same event again fails.
+    let response = api::leave_event(&env, issuer_id, authorized_principal, "event A")?;
+    assert_matches!(response, Err(EarlyAdopterError::External(msg)) if msg.contains("not registered for event"));
+
+    Ok(())
+}
+
+#[test]
+fn should_not_issue_credentials_after_deleting_my_data() -> Result<(), CallError> {
+    let env = env();
+    let issuer_id = install_issuer(&env, &DUMMY_ISSUER_INIT);
+    let authorized_principal = Principal::from_text(DUMMY_ALIAS_ID_DAPP_PRINCIPAL).unwrap();
+    let event_name = "DICE2024".to_string();
+    let event_code = "code".to_string();
+    let event_request = AddEventRequest {
+        event_name: event_name.clone(),
+        registration_code: Some(event_code.clone()),
+        tags: None,
+    };
+    api::add_event(&env, issuer_id, controller(), &event_request)?.expect("API call failed");
+    let request = RegisterUserRequest {
+        event_data: Some(RegisterUserEventData {
+            event_name: event_name.clone(),
+            registration_code: event_code,
+        }),
+    };
+    api::register_early_adopter(&env, issuer_id, authorized_principal, &request)?
+        .expect("Failed registering user");
+
+    api::delete_my_data(&env, issuer_id, authorized_principal)?.expect("Failed deleting data");
+
+    assert!(api::get_my_registration(&env, issuer_id, authorized_principal)?.is_none());
+    for credential_spec in [
+        early_adopter_credential_spec(),
+        event_attendance_credential_spec(event_name.clone()),
+    ] {
+        let response = api::prepare_credential(
+            &env,
+            issuer_id,
+            authorized_principal,
+            &PrepareCredentialRequest {
+                credential_spec,
+                signed_id_alias: DUMMY_SIGNED_ID_ALIAS.clone(),
+            },
+        )?;
+        assert_matches!(response, Err(IssueCredentialError::UnauthorizedSubject(e)) if e.contains("unregistered principal"));
+    }
+    let attendees = api::list_event_attendees(
+        &env,
+        issuer_id,
+        controller(),
+        &ListEventAttendeesRequest {
+            event_name,
+            start_after: None,
+            max_results: None,
+        },
+    )?
+    .expect("API call to list attendees failed");
+    assert_eq!(attendees.total_count, 0);
+
+    // Deleting twice fails.
+    let response = api::delete_my_data(&env, issuer_id, authorized_principal)?;
+    assert_matches!(response, Err(EarlyAdopterError::External(msg)) if msg.contains("not registered"));
+
+    let deletions = api::list_deletions(&env, issuer_id, controller(), None)?
+        .expect("API call to list deletions failed");
+    assert_eq!(deletions.len(), 1);
+    assert!(!deletions[0].deleted_by_admin);
+    assert_eq!(deletions[0].events_count, 1);
+
+    Ok(())
+}
+
+#[test]
+fn only_controllers_can_delete_users() -> Result<(), CallError> {
+    let env = env();
+    let issuer_id = install_issuer(&env, &DUMMY_ISSUER_INIT);
+    let request = RegisterUserRequest { event_data: None };
+    api::register_early_adopter(&env, issuer_id, principal_1(), &request)?
+        .expect("Failed registering user");
+
+    let response = api::delete_user(&env, issuer_id, principal_2(), principal_1())?;
+    assert_matches!(response, Err(EarlyAdopterError::External(msg)) if msg.contains("Only controllers"));
+    let response = api::list_deletions(&env, issuer_id, principal_2(), None)?;
+    assert_matches!(response, Err(EarlyAdopterError::External(msg)) if msg.contains("Only controllers"));
+    assert!(api::get_my_registration(&env, issuer_id, principal_1())?.is_some());
 
-    // Re-register user b, it's timestamp should not change
-    let status_2_user_b = api::register_early_adopter(&env, issuer_id, user_b, &request)?
-        .expect("Failed getting status for user b");
-    assert_eq!(
-        status_1_user_b.joined_timestamp_s,
-        status_2_user_b.joined_timestamp_s
-    );
+    api::delete_user(&env, issuer_id, controller(), principal_1())?.expect("Failed deleting user");
+    assert!(api::get_my_registration(&env, issuer_id, principal_1())?.is_none());
+    let deletions = api::list_deletions(&env, issuer_id, controller(), None)?
+        .expect("API call to list deletions failed");
+    assert_eq!(deletions.len(), 1);
+    assert!(deletions[0].deleted_by_admin);
 
     Ok(())
 }
@@ -1065,11 +2662,13 @@ fn should_add_events_to_registered_user() -> Result<(), CallError> {
     let event_request_a = AddEventRequest {
         event_name: event_name_a.clone(),
         registration_code: Some(event_code_a.clone()),
+        tags: None,
     };
     let _ = api::add_event(&env, issuer_id, controller(), &event_request_a).unwrap();
     let event_request_b = AddEventRequest {
         event_name: event_name_b.clone(),
         registration_code: Some(event_code_b.clone()),
+        tags: None,
     };
     let _ = api::add_event(&env, issuer_id, controller(), &event_request_b).unwrap();
 
@@ -1098,6 +2697,337 @@ fn should_add_events_to_registered_user() -> Result<(), CallError> {
     Ok(())
 }
 
+#[test]
+fn should_persist_events_added_to_registered_user() -> Result<(), CallError> {
+    let env = env();
+    let issuer_id = install_issuer(&env, &DUMMY_ISSUER_INIT);
+    let user = principal_1();
+    for event_name in ["event A", "event B"] {
+        let add_event_request = AddEventRequest {
+            event_name: event_name.to_string(),
+            registration_code: Some("code".to_string()),
+            tags: None,
+        };
+        api::add_event(&env, issuer_id, controller(), &add_event_request)?
+            .expect("API call failed");
+        let register_request = RegisterUserRequest {
+            event_data: Some(RegisterUserEventData {
+                event_name: event_name.to_string(),
+                registration_code: "code".to_string(),
+            }),
+        };
+        api::register_early_adopter(&env, issuer_id, user, &register_request)?
+            .expect("Failed registering user");
+    }
+
+    // The event added by the second registration is stored, not only returned.
+    let registration =
+        api::get_my_registration(&env, issuer_id, user)?.expect("user is not registered");
+    let event_names: Vec<&str> = registration
+        .events
+        .iter()
+        .map(|event| event.event_name.as_str())
+        .collect();
+    assert_eq!(event_names, vec!["event A", "event B"]);
+
+    Ok(())
+}
+
+#[test]
+fn should_list_event_attendees() -> Result<(), CallError> {
+    let env = env();
+    let issuer_id = install_issuer(&env, &DUMMY_ISSUER_INIT);
+    let event_name = "event A".to_string();
+    let event_code = "code A".to_string();
+    let add_event_request = AddEventRequest {
+        event_name: event_name.clone(),
+        registration_code: Some(event_code.clone()),
+        tags: None,
+    };
+    api::add_event(&env, issuer_id, controller(), &add_event_request)?.expect("API call failed");
+    let register_request = RegisterUserRequest {
+        event_data: Some(RegisterUserEventData {
+            event_name: event_name.clone(),
+            registration_code: event_code,
+        }),
+    };
+    // The first user registers without an event and joins the event later.
+    api::register_early_adopter(
+        &env,
+        issuer_id,
+        principal_1(),
+        &RegisterUserRequest { event_data: None },
+    )?
+    .expect("Failed registering user");
+    for user in [principal_1(), principal_2(), test_principal(3)] {
+        api::register_early_adopter(&env, issuer_id, user, &register_request)?
+            .expect("Failed registering user");
+    }
+
+    let response = api::list_event_attendees(
+        &env,
+        issuer_id,
+        principal_1(),
+        &ListEventAttendeesRequest {
+            event_name: event_name.clone(),
+            start_after: None,
+            max_results: None,
+        },
+    )?;
+    assert_matches!(response, Err(EarlyAdopterError::External(msg)) if msg.contains("Only controllers"));
+
+    let first_page = api::list_event_attendees(
+        &env,
+        issuer_id,
+        controller(),
+        &ListEventAttendeesRequest {
+            event_name: event_name.clone(),
+            start_after: None,
+            max_results: Some(2),
+        },
+    )?
+    .expect("API call to list attendees failed");
+    assert_eq!(first_page.total_count, 3);
+    assert_eq!(first_page.attendees.len(), 2);
+    assert!(first_page.next_start_after.is_some());
+
+    let second_page = api::list_event_attendees(
+        &env,
+        issuer_id,
+        controller(),
+        &ListEventAttendeesRequest {
+            event_name: event_name.clone(),
+            start_after: first_page.next_start_after,
+            max_results: Some(2),
+        },
+    )?
+    .expect("API call to list attendees failed");
+    assert_eq!(second_page.attendees.len(), 1);
+    assert_eq!(second_page.next_start_after, None);
+
+    let mut attendees: Vec<Principal> = first_page
+        .attendees
+        .iter()
+        .chain(second_page.attendees.iter())
+        .map(|attendee| attendee.principal)
+        .collect();
+    attendees.sort();
+    let mut expected = vec![principal_1(), principal_2(), test_principal(3)];
+    expected.sort();
+    assert_eq!(attendees, expected);
+
+    // Attendees that leave the event are no longer counted.
+    api::leave_event(&env, issuer_id, principal_2(), &event_name)?
+        .expect("API call to leave event failed");
+    let attendees = api::list_event_attendees(
+        &env,
+        issuer_id,
+        controller(),
+        &ListEventAttendeesRequest {
+            event_name,
+            start_after: None,
+            max_results: None,
+        },
+    )?
+    .expect("API call to list attendees failed");
+    assert_eq!(attendees.total_count, 2);
+    assert_eq!(attendees.attendees.len(), 2);
+
+    Ok(())
+}
+
+#[test]
+fn should_import_event_attendees() -> Result<(), CallError> {
+    let env = env();
+    let issuer_id = install_issuer(&env, &DUMMY_ISSUER_INIT);
+    let authorized_principal = Principal::from_text(DUMMY_ALIAS_ID_DAPP_PRINCIPAL).unwrap();
+    let event_name = "DICE2024".to_string();
+    let add_event_request = AddEventRequest {
+        event_name: event_name.clone(),
+        registration_code: None,
+        tags: None,
+    };
+    let event = api::add_event(&env, issuer_id, controller(), &add_event_request)?
+        .expect("API call failed");
+    env.advance_time(Duration::from_secs(2 * 3600));
+    let now_s = (time(&env) / 1_000_000_000) as u32;
+    let request = ImportEventAttendeesRequest {
+        event_name: event_name.clone(),
+        attendees: vec![
+            ImportedAttendee {
+                principal: authorized_principal,
+                joined_timestamp_s: Some(now_s - 3600),
+            },
+            ImportedAttendee {
+                principal: principal_1(),
+                joined_timestamp_s: None,
+            },
+            ImportedAttendee {
+                principal: Principal::anonymous(),
+                joined_timestamp_s: None,
+            },
+            ImportedAttendee {
+                principal: principal_2(),
+                joined_timestamp_s: Some(now_s + 3600),
+            },
+            ImportedAttendee {
+                principal: test_principal(3),
+                joined_timestamp_s: Some(event.created_timestamp_s - 1),
+            },
+        ],
+    };
+
+    let response = api::import_event_attendees(&env, issuer_id, principal_1(), &request)?;
+    assert_matches!(response, Err(EarlyAdopterError::External(msg)) if msg.contains("Only controllers"));
+
+    let response = api::import_event_attendees(&env, issuer_id, controller(), &request)?
+        .expect("API call to import attendees failed");
+    assert_eq!(response.results.len(), 5);
+    assert_eq!(response.results[0].1, ImportAttendeeResult::Registered);
+    assert_eq!(response.results[1].1, ImportAttendeeResult::Registered);
+    assert_matches!(response.results[2].1, ImportAttendeeResult::Rejected(_));
+    assert_matches!(response.results[3].1, ImportAttendeeResult::Rejected(_));
+    assert_matches!(&response.results[4].1, ImportAttendeeResult::Rejected(msg) if msg.contains("before the event was created"));
+
+    // New users are registered at the time of the import, and join the event at the check-in.
+    let registration = api::get_my_registration(&env, issuer_id, authorized_principal)?
+        .expect("user is not registered");
+    assert_eq!(registration.joined_timestamp_s, now_s);
+    assert_eq!(registration.events.len(), 1);
+    assert_eq!(registration.events[0].joined_timestamp_s, now_s - 3600);
+    let response = api::prepare_credential(
+        &env,
+        issuer_id,
+        authorized_principal,
+        &PrepareCredentialRequest {
+            credential_spec: event_attendance_credential_spec(event_name.clone()),
+            signed_id_alias: DUMMY_SIGNED_ID_ALIAS.clone(),
+        },
+    )?;
+    assert_matches!(response, Ok(_));
+
+    // The import is idempotent.
+    let response = api::import_event_attendees(&env, issuer_id, controller(), &request)?
+        .expect("API call to import attendees failed");
+    assert_eq!(
+        response.results[0].1,
+        ImportAttendeeResult::AlreadyRegistered
+    );
+    assert_eq!(
+        response.results[1].1,
+        ImportAttendeeResult::AlreadyRegistered
+    );
+    let registration = api::get_my_registration(&env, issuer_id, authorized_principal)?
+        .expect("user is not registered");
+    assert_eq!(registration.joined_timestamp_s, now_s);
+
+    Ok(())
+}
+
+#[test]
+fn should_export_and_import_state() -> Result<(), CallError> {
+    let env = env();
+    let source_id = install_issuer(&env, &DUMMY_ISSUER_INIT);
+    let target_id = install_issuer(&env, &DUMMY_ISSUER_INIT);
+    let authorized_principal = Principal::from_text(DUMMY_ALIAS_ID_DAPP_PRINCIPAL).unwrap();
+    let event_name = "DICE2024".to_string();
+    let add_event_request = AddEventRequest {
+        event_name: event_name.clone(),
+        registration_code: None,
+        tags: None,
+    };
+    let event = api::add_event(&env, source_id, controller(), &add_event_request)?
+        .expect("API call failed");
+    let event_data = RegisterUserEventData {
+        event_name: event.event_name.clone(),
+        registration_code: event.registration_code.clone(),
+    };
+    for principal in [authorized_principal, principal_1()] {
+        api::register_early_adopter(
+            &env,
+            source_id,
+            principal,
+            &RegisterUserRequest {
+                event_data: Some(event_data.clone()),
+            },
+        )?
+        .expect("failed registering user");
+    }
+
+    let response = api::export_state(&env, source_id, principal_1(), None)?;
+    assert_matches!(response, Err(EarlyAdopterError::External(msg)) if msg.contains("Only controllers"));
+
+    let mut cursor = None;
+    let mut events_imported = 0;
+    let mut early_adopters_imported = 0;
+    loop {
+        let chunk = api::export_state(&env, source_id, controller(), cursor)?
+            .expect("API call to export state failed");
+        let response = api::import_state(&env, target_id, principal_1(), &chunk)?;
+        assert_matches!(response, Err(EarlyAdopterError::External(msg)) if msg.contains("Only controllers"));
+
+        let mut corrupted_chunk = chunk.clone();
+        corrupted_chunk.data.push(0);
+        let response = api::import_state(&env, target_id, controller(), &corrupted_chunk)?;
+        assert_matches!(response, Err(EarlyAdopterError::External(msg)) if msg.contains("checksum"));
+
+        let response = api::import_state(&env, target_id, controller(), &chunk)?
+            .expect("API call to import state failed");
+        events_imported += response.events_imported;
+        early_adopters_imported += response.early_adopters_imported;
+        cursor = chunk.next;
+        if cursor.is_none() {
+            break;
+        }
+    }
+    assert_eq!(events_imported, 1);
+    assert_eq!(early_adopters_imported, 2);
+
+    let events = api::list_events(&env, target_id, controller())?.expect("API call failed");
+    assert_eq!(events.events.len(), 1);
+    assert_eq!(events.events[0].event_name, event_name);
+    assert_eq!(
+        events.events[0].registration_code,
+        Some(event.registration_code)
+    );
+    for principal in [authorized_principal, principal_1()] {
+        assert_eq!(
+            format!(
+                "{:?}",
+                api::get_my_registration(&env, target_id, principal)?
+            ),
+            format!(
+                "{:?}",
+                api::get_my_registration(&env, source_id, principal)?
+            )
+        );
+    }
+    let attendees = api::list_event_attendees(
+        &env,
+        target_id,
+        controller(),
+        &ListEventAttendeesRequest {
+            event_name: event_name.clone(),
+            start_after: None,
+            max_results: None,
+        },
+    )?
+    .expect("API call failed");
+    assert_eq!(attendees.total_count, 2);
+    let response = api::prepare_credential(
+        &env,
+        target_id,
+        authorized_principal,
+        &PrepareCredentialRequest {
+            credential_spec: event_attendance_credential_spec(event_name),
+            signed_id_alias: DUMMY_SIGNED_ID_ALIAS.clone(),
+        },
+    )?;
+    assert_matches!(response, Ok(_));
+
+    Ok(())
+}
+
 #[test]
 fn should_fail_to_register_user_with_empty_event_name() -> Result<(), CallError> {
     let env = env();
@@ -1130,6 +3060,7 @@ fn only_controllers_can_add_events() -> Result<(), CallError> {
     let empty_event = AddEventRequest {
         event_name: "Test".to_string(),
         registration_code: None,
+        tags: None,
     };
 
     let response = api::add_event(&env, issuer_id, user, &empty_event)?.unwrap_err();
@@ -1151,6 +3082,7 @@ fn should_add_event_with_random_code() -> Result<(), CallError> {
     let empty_event = AddEventRequest {
         event_name: "Test".to_string(),
         registration_code: None,
+        tags: None,
     };
 
     api::add_event(&env, issuer_id, controller(), &empty_event)?
@@ -1173,6 +3105,7 @@ fn should_add_event_with_code() -> Result<(), CallError> {
     let empty_event = AddEventRequest {
         event_name: "Test".to_string(),
         registration_code: Some(code.clone()),
+        tags: None,
     };
 
     api::add_event(&env, issuer_id, controller(), &empty_event)?.expect("API call failed");
@@ -1197,6 +3130,7 @@ fn should_not_register_same_event_name_twice() -> Result<(), CallError> {
     let empty_event = AddEventRequest {
         event_name: "Test".to_string(),
         registration_code: Some(code.clone()),
+        tags: None,
     };
 
     api::add_event(&env, issuer_id, controller(), &empty_event)?.expect("API call failed");
@@ -1220,14 +3154,17 @@ fn should_add_events() -> Result<(), CallError> {
     let event_1 = AddEventRequest {
         event_name: "Test 1".to_string(),
         registration_code: None,
+        tags: None,
     };
     let event_2 = AddEventRequest {
         event_name: "Test 2".to_string(),
         registration_code: None,
+        tags: None,
     };
     let event_3 = AddEventRequest {
         event_name: "Test 3".to_string(),
         registration_code: None,
+        tags: None,
     };
 
     api::add_event(&env, issuer_id, controller(), &event_1)?.expect("API call failed");
@@ -1242,10 +3179,159 @@ fn should_add_events() -> Result<(), CallError> {
     Ok(())
 }
 
+#[test]
+fn should_list_events_page() -> Result<(), CallError> {
+    let env = env();
+    let issuer_id = install_issuer(&env, &DUMMY_ISSUER_INIT);
+    for (event_name, tags) in [
+        ("Test 1", None),
+        ("Test 2", Some(vec!["meetup".to_string()])),
+        (
+            "Test 3",
+            Some(vec!["meetup".to_string(), "zurich".to_string()]),
+        ),
+    ] {
+        let request = AddEventRequest {
+            event_name: event_name.to_string(),
+            registration_code: None,
+            tags,
+        };
+        api::add_event(&env, issuer_id, controller(), &request)?.expect("API call failed");
+    }
+
+    let first_page = api::list_events_page(
+        &env,
+        issuer_id,
+        principal_1(),
+        &ListEventsPageRequest {
+            max_results: Some(2),
+            ..ListEventsPageRequest::default()
+        },
+    )?
+    .expect("API to list events failed");
+    assert_eq!(first_page.events.len(), 2);
+    assert_eq!(first_page.events[0].event_name, "Test 1");
+    assert_eq!(first_page.events[0].registration_code, None);
+    assert_eq!(first_page.next_start_after, Some("Test 2".to_string()));
+
+    let second_page = api::list_events_page(
+        &env,
+        issuer_id,
+        principal_1(),
+        &ListEventsPageRequest {
+            start_after: first_page.next_start_after,
+            max_results: Some(2),
+            ..ListEventsPageRequest::default()
+        },
+    )?
+    .expect("API to list events failed");
+    assert_eq!(second_page.events.len(), 1);
+    assert_eq!(second_page.events[0].event_name, "Test 3");
+    assert_eq!(second_page.next_start_after, None);
+
+    let tagged = api::list_events_page(
+        &env,
+        issuer_id,
+        principal_1(),
+        &ListEventsPageRequest {
+            tag: Some("meetup".to_string()),
+            ..ListEventsPageRequest::default()
+        },
+    )?
+    .expect("API to list events failed");
+    assert_eq!(tagged.events.len(), 2);
+
+    // At least one event is returned, as an empty page would not advance the cursor.
+    let single = api::list_events_page(
+        &env,
+        issuer_id,
+        principal_1(),
+        &ListEventsPageRequest {
+            max_results: Some(0),
+            ..ListEventsPageRequest::default()
+        },
+    )?
+    .expect("API to list events failed");
+    assert_eq!(single.events.len(), 1);
+    assert_eq!(single.next_start_after, Some("Test 1".to_string()));
+
+    Ok(())
+}
+
+#[test]
+fn should_filter_archived_events() -> Result<(), CallError> {
+    let env = env();
+    let issuer_id = install_issuer(&env, &DUMMY_ISSUER_INIT);
+    for event_name in ["Test 1", "Test 2"] {
+        let request = AddEventRequest {
+            event_name: event_name.to_string(),
+            registration_code: None,
+            tags: None,
+        };
+        api::add_event(&env, issuer_id, controller(), &request)?.expect("API call failed");
+    }
+    let archive_request = ArchiveEventRequest {
+        event_name: "Test 1".to_string(),
+        archived: true,
+    };
+    let response = api::archive_event(&env, issuer_id, principal_1(), &archive_request)?;
+    assert_matches!(response, Err(EarlyAdopterError::External(msg)) if msg.contains("Only controllers"));
+    let archived_event = api::archive_event(&env, issuer_id, controller(), &archive_request)?
+        .expect("API call to archive event failed");
+    assert!(archived_event.archived);
+
+    let active = api::list_events_page(
+        &env,
+        issuer_id,
+        controller(),
+        &ListEventsPageRequest::default(),
+    )?
+    .expect("API to list events failed");
+    assert_eq!(active.events.len(), 1);
+    assert_eq!(active.events[0].event_name, "Test 2");
+    assert_matches!(active.events[0].registration_code, Some(_));
+
+    let archived = api::list_events_page(
+        &env,
+        issuer_id,
+        controller(),
+        &ListEventsPageRequest {
+            archived: Some(true),
+            ..ListEventsPageRequest::default()
+        },
+    )?
+    .expect("API to list events failed");
+    assert_eq!(archived.events.len(), 1);
+    assert_eq!(archived.events[0].event_name, "Test 1");
+
+    // Unarchived events are listed again.
+    api::archive_event(
+        &env,
+        issuer_id,
+        controller(),
+        &ArchiveEventRequest {
+            archived: false,
+            ..archive_request
+        },
+    )?
+    .expect("API call to unarchive event failed");
+    let active = api::list_events_page(
+        &env,
+        issuer_id,
+        controller(),
+        &ListEventsPageRequest::default(),
+    )?
+    .expect("API to list events failed");
+    assert_eq!(active.events.len(), 2);
+
+    Ok(())
+}
+
 #[test]
 fn should_upgrade_issuer() -> Result<(), CallError> {
     let env = env();
     let issuer_id = install_issuer(&env, &DUMMY_ISSUER_INIT);
+    assert_metrics(&env, issuer_id, "stable_memory_schema_version 4")?;
     let arg = candid::encode_one("()").expect("error encoding issuer init arg as candid");
     env.upgrade_canister(
         issuer_id,
@@ -1264,6 +3350,155 @@ fn should_upgrade_issuer() -> Result<(), CallError> {
     let _ = api::vc_consent_message(&env, issuer_id, principal_1(), &consent_message_request)
         .expect("API call failed")
         .expect("Failed to obtain consent info");
+    assert_metrics(&env, issuer_id, "stable_memory_schema_version 4")?;
+    Ok(())
+}
+
+/// Upgrades from the Wasm module of the previous release, see the README.
+#[test]
+fn should_upgrade_issuer_from_previous_release() -> Result<(), CallError> {
+    let env = env();
+    let canister_controller = controller();
+    let settings = CanisterSettings {
+        controllers: Some(vec![canister_controller]),
+        compute_allocation: None,
+        memory_allocation: None,
+        freezing_threshold: None,
+    };
+    let issuer_id = env.create_canister_with_settings(Some(settings), Some(canister_controller));
+    let init = IssuerInit {
+        derivation_origin: "https://previous.derivation.origin".to_string(),
+        frontend_hostname: "https://previous.frontend.host.name".to_string(),
+        ..IssuerInit::default()
+    };
+    let arg = candid::encode_one(Some(&init)).expect("error encoding issuer init arg as candid");
+    env.install_canister(
+        issuer_id,
+        EARLY_ADOPTER_ISSUER_PREVIOUS_WASM.clone(),
+        arg,
+        Some(canister_controller),
+    );
+    let mut events = vec![];
+    for event_name in ["DICE2024", "ETHDenver"] {
+        let add_event_request = AddEventRequest {
+            event_name: event_name.to_string(),
+            registration_code: None,
+            tags: None,
+        };
+        events.push(
+            api::add_event(&env, issuer_id, controller(), &add_event_request)?
+                .expect("API call failed"),
+        );
+    }
+    let event = &events[0];
+    let authorized_principal = Principal::from_text(DUMMY_ALIAS_ID_DAPP_PRINCIPAL).unwrap();
+    let request = RegisterUserRequest {
+        event_data: Some(RegisterUserEventData {
+            event_name: event.event_name.clone(),
+            registration_code: event.registration_code.clone(),
+        }),
+    };
+    let status_before =
+        api::register_early_adopter(&env, issuer_id, authorized_principal, &request)?
+            .expect("Failed registering");
+    env.advance_time(Duration::from_secs(2));
+    let other_status_before = api::register_early_adopter(
+        &env,
+        issuer_id,
+        principal_1(),
+        &RegisterUserRequest { event_data: None },
+    )?
+    .expect("Failed registering");
+
+    // The records re-encoded by the migrations are the ones written by the previous release.
+    let assert_upgraded_state = || -> Result<(), CallError> {
+        assert_metrics(&env, issuer_id, "stable_memory_schema_version 4")?;
+        let status_after = api::get_my_registration(&env, issuer_id, authorized_principal)?
+            .expect("user is not registered");
+        assert_eq!(
+            format!("{:?}", status_before),
+            format!("{:?}", status_after)
+        );
+        let other_status_after = api::get_my_registration(&env, issuer_id, principal_1())?
+            .expect("user is not registered");
+        assert_eq!(
+            format!("{:?}", other_status_before),
+            format!("{:?}", other_status_after)
+        );
+
+        let events_after =
+            api::list_events(&env, issuer_id, controller())?.expect("API call failed");
+        assert_eq!(events_after.events.len(), events.len());
+        for (before, after) in events.iter().zip(&events_after.events) {
+            assert_eq!(after.event_name, before.event_name);
+            assert_eq!(
+                after.registration_code,
+                Some(before.registration_code.clone())
+            );
+            assert_eq!(after.created_timestamp_s, before.created_timestamp_s);
+            assert!(after.tags.is_empty());
+            assert!(!after.archived);
+        }
+
+        let derivation_origin = api::derivation_origin(
+            &env,
+            issuer_id,
+            principal_1(),
+            &DerivationOriginRequest {
+                frontend_hostname: init.frontend_hostname.clone(),
+            },
+        )?
+        .expect("derivation_origin error");
+        assert_eq!(derivation_origin.origin, init.derivation_origin);
+
+        // The attendee index was backfilled.
+        let attendees = api::list_event_attendees(
+            &env,
+            issuer_id,
+            controller(),
+            &ListEventAttendeesRequest {
+                event_name: event.event_name.clone(),
+                start_after: None,
+                max_results: None,
+            },
+        )?
+        .expect("API call failed");
+        assert_eq!(attendees.total_count, 1);
+        assert_eq!(attendees.attendees[0].principal, authorized_principal);
+        assert_eq!(
+            attendees.attendees[0].joined_timestamp_s,
+            status_before.events[0].joined_timestamp_s
+        );
+        Ok(())
+    };
+
+    let arg = candid::encode_one("()").expect("error encoding issuer init arg as candid");
+    env.upgrade_canister(
+        issuer_id,
+        EARLY_ADOPTER_ISSUER_WASM.clone(),
+        arg.clone(),
+        Some(controller()),
+    )?;
+    assert_upgraded_state()?;
+    let response = api::prepare_credential(
+        &env,
+        issuer_id,
+        authorized_principal,
+        &PrepareCredentialRequest {
+            credential_spec: event_attendance_credential_spec(event.event_name.clone()),
+            signed_id_alias: DUMMY_SIGNED_ID_ALIAS.clone(),
+        },
+    )?;
+    assert_matches!(response, Ok(_));
+
+    // Upgrading again with nothing left to migrate keeps the data.
+    env.upgrade_canister(
+        issuer_id,
+        EARLY_ADOPTER_ISSUER_WASM.clone(),
+        arg,
+        Some(controller()),
+    )?;
+    assert_upgraded_state()?;
     Ok(())
 }
 
@@ -0,0 +1,55 @@
+//! Proptest strategies and credential spec fixtures shared by the tests.
+
+use proptest::collection::hash_map;
+use proptest::option;
+use proptest::prelude::*;
+use std::collections::HashMap;
+use vc_util::issuer_api::{ArgumentValue, CredentialSpec};
+
+/// Builds the credential spec of the given type with the given arguments.
+pub fn credential_spec(
+    credential_type: &str,
+    arguments: &[(&str, ArgumentValue)],
+) -> CredentialSpec {
+    CredentialSpec {
+        credential_type: credential_type.to_string(),
+        arguments: Some(
+            arguments
+                .iter()
+                .map(|(name, value)| (name.to_string(), value.clone()))
+                .collect::<HashMap<_, _>>(),
+        ),
+    }
+}
+
+/// Argument values of any type, with extreme integers and arbitrary Unicode strings.
+pub fn arb_argument_value() -> impl Strategy<Value = ArgumentValue> {
+    prop_oneof![
+        any::<i32>().prop_map(ArgumentValue::Int),
+        (2020..2030i32).prop_map(ArgumentValue::Int),
+        "\\PC{0,40}".prop_map(ArgumentValue::String),
+    ]
+}
+
+/// Credential specs that are mostly, but not always, close to the supported ones:
+/// known or arbitrary credential types, with missing, extra or mistyped arguments.
+pub fn arb_credential_spec() -> impl Strategy<Value = CredentialSpec> {
+    let credential_type = prop_oneof![
+        Just("EarlyAdopter".to_string()),
+        Just("EventAttendance".to_string()),
+        "\\PC{0,20}",
+    ];
+    let argument_name = prop_oneof![
+        Just("sinceYear".to_string()),
+        Just("eventName".to_string()),
+        "\\PC{0,12}",
+    ];
+    (
+        credential_type,
+        option::of(hash_map(argument_name, arb_argument_value(), 0..4)),
+    )
+        .prop_map(|(credential_type, arguments)| CredentialSpec {
+            credential_type,
+            arguments,
+        })
+}